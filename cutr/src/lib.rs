@@ -1,13 +1,16 @@
 use crate::Extract::*;
 use clap::{App, Arg};
-use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use csv::{ReaderBuilder, StringRecord};
 use regex::Regex;
 use std::{
+    borrow::Cow,
+    cell::RefCell,
     error::Error,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read, Write},
     num::NonZeroUsize,
     ops::Range,
+    rc::Rc,
 };
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
@@ -20,10 +23,30 @@ pub enum Extract {
     Chars(PositionList),
 }
 
+// -fでの行の分け方。plainはPOSIXのcutと同じく区切り文字で分けるだけで、
+// csvはRFC 4180の引用符を解釈する。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Plain,
+    Csv(QuoteStyle),
+}
+
+// csvで出力するフィールドを引用符で囲む条件。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Always,
+    Necessary,
+    NonNumeric,
+    Never,
+}
+
 #[derive(Debug)]
 pub struct Config {
     files: Vec<String>,
     delimiter: u8,
+    output_delimiter: String,
+    only_delimited: bool,
+    mode: Mode,
     extract: Extract,
 }
 
@@ -32,6 +55,8 @@ pub fn get_args() -> MyResult<Config> {
     .version("0.1.0")
     .author("naoyuki miayta(n.miyata080825@gmail.com")
     .about("rust cut")
+    .after_help("LIST is one or more ranges separated by commas or blanks. Each range is \
+N (the Nth), N-M (Nth to Mth), N- (Nth to the end of the line) or -M (first to Mth).")
     .arg(
         Arg::with_name("files")
         .value_name("FILES")
@@ -46,6 +71,7 @@ pub fn get_args() -> MyResult<Config> {
         .short("b")
         .long("bytes")
         .takes_value(true)
+        .allow_hyphen_values(true)
         .conflicts_with_all(&["chars", "fields"]),
     )
     .arg(
@@ -55,6 +81,7 @@ pub fn get_args() -> MyResult<Config> {
         .short("c")
         .long("characters")
         .takes_value(true)
+        .allow_hyphen_values(true)
         .conflicts_with_all(&["bytes", "fields"]),
     )
     .arg(
@@ -73,8 +100,47 @@ pub fn get_args() -> MyResult<Config> {
         .short("f")
         .long("fields")
         .takes_value(true)
+        .allow_hyphen_values(true)
         .conflicts_with_all(&["bytes", "chars"]),
     )
+    .arg(
+        Arg::with_name("only_delimited")
+        .help("do not print lines not containing delimiters")
+        .short("s")
+        .long("only-delimited")
+        .requires("fields"),
+    )
+    .arg(
+        Arg::with_name("output_delimiter")
+        .value_name("STRING")
+        .help("use STRING as the output delimiter [default: the input delimiter]")
+        .long("output-delimiter")
+        .takes_value(true)
+        .requires("fields"),
+    )
+    .arg(
+        Arg::with_name("mode")
+        .value_name("MODE")
+        .help("split fields on every delimiter (plain) or honour CSV quoting (csv)")
+        .long("mode")
+        .takes_value(true)
+        .possible_values(&["plain", "csv"])
+        .default_value("csv"),
+    )
+    .arg(
+        Arg::with_name("quote")
+        .value_name("STYLE")
+        .help("quote output fields in csv mode: always, necessary (default), non-numeric or never")
+        .long("quote")
+        .takes_value(true)
+        .possible_values(&["always", "necessary", "non-numeric", "never"])
+        .requires("fields"),
+    )
+    .arg(
+        Arg::with_name("complement")
+        .help("select everything except the fields, bytes or characters in LIST")
+        .long("complement"),
+    )
     .get_matches();
 
     let delimiter = matches.value_of("delimiter").unwrap();
@@ -86,9 +152,30 @@ pub fn get_args() -> MyResult<Config> {
         )));
     }
 
-    let fields = matches.value_of("fields").map(parse_pos).transpose()?;
-    let bytes = matches.value_of("bytes").map(parse_pos).transpose()?;
-    let chars = matches.value_of("chars").map(parse_pos).transpose()?;
+    let quote_style = match matches.value_of("quote") {
+        Some("always") => QuoteStyle::Always,
+        Some("non-numeric") => QuoteStyle::NonNumeric,
+        Some("never") => QuoteStyle::Never,
+        _ => QuoteStyle::Necessary,
+    };
+    let mode = match matches.value_of("mode") {
+        Some("plain") if matches.is_present("quote") => {
+            return Err(From::from("--quote cannot be used with --mode=plain"));
+        }
+        Some("plain") => Mode::Plain,
+        _ => Mode::Csv(quote_style),
+    };
+
+    let complement = matches.is_present("complement");
+    let parse = |list: &str| {
+        parse_pos(list).map(|pos| match complement {
+            true => invert(&pos),
+            false => pos,
+        })
+    };
+    let fields = matches.value_of("fields").map(parse).transpose()?;
+    let bytes = matches.value_of("bytes").map(parse).transpose()?;
+    let chars = matches.value_of("chars").map(parse).transpose()?;
 
     let extract = if let Some(field_pos) = fields {
         Fields(field_pos)
@@ -103,6 +190,12 @@ pub fn get_args() -> MyResult<Config> {
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         delimiter: *delimiter_bytes.first().unwrap(),
+        output_delimiter: matches
+            .value_of("output_delimiter")
+            .unwrap_or(delimiter)
+            .to_string(),
+        only_delimited: matches.is_present("only_delimited"),
+        mode,
         extract: extract,
     })
 }
@@ -112,20 +205,10 @@ pub fn run(config: Config) -> MyResult<()> {
         match open(filename) {
             Err(e) => eprintln!("{}: {}", filename, e),
             Ok(file) => match &config.extract {
-                Fields(field_pos) => {
-                    let mut reader = ReaderBuilder::new()
-                        .delimiter(config.delimiter)
-                        .has_headers(false)
-                        .from_reader(file);
-                    let mut writer = WriterBuilder::new()
-                        .delimiter(config.delimiter)
-                        .from_writer(io::stdout());
-
-                    for record in reader.records() {
-                        let record = record?;
-                        writer.write_record(extract_fields(&record, field_pos))?
-                    }
-                }
+                Fields(field_pos) => match config.mode {
+                    Mode::Plain => cut_plain(file, field_pos, &config)?,
+                    Mode::Csv(quote_style) => cut_csv(file, field_pos, quote_style, &config)?,
+                },
                 Bytes(byte_pos) => {
                     for line in file.lines() {
                         println!("{}", extract_bytes(&line?, byte_pos));
@@ -133,16 +216,152 @@ pub fn run(config: Config) -> MyResult<()> {
                 }
                 Chars(char_pos) => {
                     for line in file.lines() {
-                        println!("{}", extract_bytes(&line?, char_pos));
+                        println!("{}", extract_chars(&line?, char_pos));
                     }
                 }
             },
         }
     }
-    println!("{:#?}", config);
     Ok(())
 }
 
+// 区切り文字のない行は、どちらのモードでも-sがなければそのまま出力する。
+fn cut_plain(
+    mut file: Box<dyn BufRead>,
+    field_pos: &[Range<usize>],
+    config: &Config,
+) -> MyResult<()> {
+    let mut out = io::stdout().lock();
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        let fields: Vec<&[u8]> = line.split(|&b| b == config.delimiter).collect();
+        if fields.len() == 1 {
+            if !config.only_delimited {
+                out.write_all(&line)?;
+                out.write_all(b"\n")?;
+            }
+            continue;
+        }
+        let selected = field_pos
+            .iter()
+            .flat_map(|range| clamp(range, fields.len()).map(|i| fields[i]));
+        for (i, field) in selected.enumerate() {
+            if i > 0 {
+                out.write_all(config.output_delimiter.as_bytes())?;
+            }
+            out.write_all(field)?;
+        }
+        out.write_all(b"\n")?;
+    }
+}
+
+fn cut_csv(
+    file: Box<dyn BufRead>,
+    field_pos: &[Range<usize>],
+    quote_style: QuoteStyle,
+    config: &Config,
+) -> MyResult<()> {
+    // 区切り文字のない行をそのまま出力するため、読んだバイト列を取っておく。
+    let seen = Rc::new(RefCell::new(vec![]));
+    let mut reader = ReaderBuilder::new()
+        .delimiter(config.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(Tee {
+            inner: file,
+            seen: Rc::clone(&seen),
+        });
+    let mut out = io::stdout().lock();
+    let mut record = StringRecord::new();
+    // seenの先頭の、入力の中での位置
+    let mut base = 0;
+    // 直前の改行が\rで終わっているか
+    let mut cr = false;
+
+    loop {
+        let more = reader.read_record(&mut record)?;
+        let mut seen = seen.borrow_mut();
+        let (start, end) = match record.position() {
+            Some(pos) if more => (
+                (pos.byte() - base) as usize,
+                (reader.position().byte() - base) as usize,
+            ),
+            _ => (seen.len(), seen.len()),
+        };
+        // csvは空行を読み飛ばし、その改行をレコードの前に含めることがある。
+        // レコードは改行では始まらないので、先頭の改行は空行として扱う。
+        let start = start
+            + seen[start..end]
+                .iter()
+                .take_while(|&&b| b == b'\r' || b == b'\n')
+                .count();
+        let blank = skip_lf(&seen[..start], &mut cr);
+        if !config.only_delimited {
+            write_raw(&mut out, blank)?;
+        }
+        if !more {
+            return Ok(());
+        }
+        let line = skip_lf(&seen[start..end], &mut cr);
+        match record.len() {
+            0 | 1 if config.only_delimited => {}
+            0 | 1 => write_raw(&mut out, line)?,
+            _ => {
+                let fields = extract_fields(&record, field_pos);
+                write_fields(&mut out, &fields, &config.output_delimiter, quote_style)?;
+            }
+        }
+        seen.drain(..end);
+        base += end as u64;
+    }
+}
+
+// csvは\r\nの\rで行を終えるので、続く\nは次に読んだ部分の先頭に来る。
+// その\nは前の改行の一部として書いているので除く。
+fn skip_lf<'a>(mut segment: &'a [u8], cr: &mut bool) -> &'a [u8] {
+    if *cr && segment.first() == Some(&b'\n') {
+        segment = &segment[1..];
+        *cr = false;
+    }
+    if !segment.is_empty() {
+        *cr = segment.ends_with(b"\r");
+    }
+    segment
+}
+
+// 入力のまま書き、改行で終わっていなければ\nを補う。
+fn write_raw(out: &mut impl Write, segment: &[u8]) -> io::Result<()> {
+    if segment.is_empty() {
+        return Ok(());
+    }
+    out.write_all(segment)?;
+    match segment.ends_with(b"\n") {
+        true => Ok(()),
+        false => out.write_all(b"\n"),
+    }
+}
+
+// 読んだバイト列をseenにも書き写す。
+struct Tee<R> {
+    inner: R,
+    seen: Rc<RefCell<Vec<u8>>>,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.seen.borrow_mut().extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -150,31 +369,75 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
+// N, N-M, N- (行末まで), -M (行頭から) をカンマか空白で区切って並べたもの。
+// N- の終わりはusize::MAXで表し、取り出すときに行の長さで打ち切る。
 fn parse_pos(range: &str) -> MyResult<PositionList> {
-    let re = Regex::new(r"^(\d+)-(\d+)$").unwrap();
+    let re = Regex::new(r"^(\d*)-(\d*)$").unwrap();
     range
-        .split(",")
-        .into_iter()
+        .split(',')
+        .flat_map(|val| match val.trim() {
+            "" => vec![val],
+            val => val.split_whitespace().collect(),
+        })
         .map(|val| {
             parse_index(val).map(|n| n..n + 1).or_else(|e| {
-                re.captures(val).ok_or(e).and_then(|captures| {
-                    let n1 = parse_index(&captures[1])?;
-                    let n2 = parse_index(&captures[2])?;
-                    if n1 > n2 {
-                        return Err(format!(
-                            "first number in range ({}) must be lower than second number ({})",
-                            n1 + 1,
-                            n2 + 1,
-                        ));
-                    }
-                    Ok(n1..n2 + 1)
-                })
+                re.captures(val)
+                    .filter(|captures| !captures[1].is_empty() || !captures[2].is_empty())
+                    .ok_or(e)
+                    .and_then(|captures| {
+                        let n1 = match &captures[1] {
+                            "" => 0,
+                            n => parse_index(n)?,
+                        };
+                        let n2 = match &captures[2] {
+                            "" => usize::MAX,
+                            n => parse_index(n)? + 1,
+                        };
+                        if n1 >= n2 {
+                            return Err(format!(
+                                "First number in range ({}) must not be greater than second number ({})",
+                                n1 + 1,
+                                n2,
+                            ));
+                        }
+                        Ok(n1..n2)
+                    })
             })
         })
         .collect::<Result<_, _>>()
         .map_err(From::from)
 }
 
+// 重なったり隣り合ったりする範囲をまとめ、昇順に並べる。
+fn normalize(pos: &[Range<usize>]) -> PositionList {
+    let mut sorted: PositionList = pos.iter().filter(|r| !r.is_empty()).cloned().collect();
+    sorted.sort_by_key(|range| range.start);
+    let mut merged: PositionList = vec![];
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+// posに含まれない位置の範囲。最後の範囲は終わりがなく、取り出すときに各行の長さで打ち切る。
+fn invert(pos: &[Range<usize>]) -> PositionList {
+    let mut inverted = vec![];
+    let mut start = 0;
+    for range in normalize(pos) {
+        if start < range.start {
+            inverted.push(start..range.start);
+        }
+        start = range.end;
+    }
+    if start < usize::MAX {
+        inverted.push(start..usize::MAX);
+    }
+    inverted
+}
+
 fn parse_index(input: &str) -> Result<usize, String> {
     let value_error = || format!("illegal list value: \"{}\"", input);
     input
@@ -188,20 +451,56 @@ fn parse_index(input: &str) -> Result<usize, String> {
         })
 }
 
+// 終わりのない範囲を長さlenで打ち切る。
+fn clamp(range: &Range<usize>, len: usize) -> Range<usize> {
+    range.start..range.end.min(len)
+}
+
 fn extract_fields<'a>(record: &'a StringRecord, field_pos: &[Range<usize>]) -> Vec<&'a str> {
     field_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| record.get(i)))
+        .flat_map(|range| clamp(range, record.len()).filter_map(|i| record.get(i)))
         .collect()
 }
 
+fn write_fields(
+    out: &mut impl Write,
+    fields: &[&str],
+    delimiter: &str,
+    quote_style: QuoteStyle,
+) -> io::Result<()> {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| quote(field, delimiter, quote_style))
+        .collect();
+    writeln!(out, "{}", fields.join(delimiter))
+}
+
+// Necessaryではcsvの既定と同じく、区切り文字や引用符、改行を含むフィールドだけを囲む。
+fn quote<'a>(field: &'a str, delimiter: &str, quote_style: QuoteStyle) -> Cow<'a, str> {
+    let necessary =
+        (!delimiter.is_empty() && field.contains(delimiter)) || field.contains(['"', '\n', '\r']);
+    let quoted = match quote_style {
+        QuoteStyle::Always => true,
+        QuoteStyle::Necessary => necessary,
+        QuoteStyle::NonNumeric => necessary || !is_numeric(field),
+        QuoteStyle::Never => false,
+    };
+    match quoted {
+        true => Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+        false => Cow::Borrowed(field),
+    }
+}
+
+fn is_numeric(field: &str) -> bool {
+    field.bytes().any(|b| b.is_ascii_digit()) && field.parse::<f64>().is_ok()
+}
+
 fn extract_bytes(line: &str, byte_pos: &[Range<usize>]) -> String {
     let bytes = line.as_bytes();
     let selected: Vec<_> = byte_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| bytes.get(i).copied()))
+        .flat_map(|range| clamp(range, bytes.len()).filter_map(|i| bytes.get(i).copied()))
         .collect();
     String::from_utf8_lossy(&selected).into_owned()
 }
@@ -210,14 +509,16 @@ fn extract_chars(line: &str, char_pos: &[Range<usize>]) -> String {
     let chars: Vec<_> = line.chars().collect();
     char_pos
         .iter()
-        .cloned()
-        .flat_map(|range| range.filter_map(|i| chars.get(i)))
+        .flat_map(|range| clamp(range, chars.len()).filter_map(|i| chars.get(i)))
         .collect()
 }
 
 #[cfg(test)]
 mod unit_tests {
-    use super::{extract_bytes, extract_chars, extract_fields, parse_pos};
+    use super::{
+        extract_bytes, extract_chars, extract_fields, invert, normalize, parse_pos, write_fields,
+        QuoteStyle,
+    };
     use csv::StringRecord;
 
     #[test]
@@ -274,28 +575,28 @@ mod unit_tests {
         let res = parse_pos("1,");
         assert!(res.is_err());
 
-        let res = parse_pos("1-");
-        assert!(res.is_err());
-
         let res = parse_pos("1-1-1");
         assert!(res.is_err());
 
         let res = parse_pos("1-1-a");
         assert!(res.is_err());
 
-        // First number must be less than second
-        let res = parse_pos("1-1");
+        let res = parse_pos("-0");
         assert!(res.is_err());
-        assert_eq!(
-            res.unwrap_err().to_string(),
-            "First number in range (1) must be lower than second number (1)"
-        );
+        assert_eq!(res.unwrap_err().to_string(), "illegal list value: \"0\"",);
 
+        let res = parse_pos("1,,2");
+        assert!(res.is_err());
+
+        let res = parse_pos(" ");
+        assert!(res.is_err());
+
+        // First number must not be greater than second
         let res = parse_pos("2-1");
         assert!(res.is_err());
         assert_eq!(
             res.unwrap_err().to_string(),
-            "First number in range (2) must be lower than second number (1)"
+            "First number in range (2) must not be greater than second number (1)"
         );
 
         // All the following are acceptable
@@ -330,6 +631,50 @@ mod unit_tests {
         let res = parse_pos("15,19-20");
         assert!(res.is_ok());
         assert_eq!(res.unwrap(), vec![14..15, 18..20]);
+
+        let res = parse_pos("1-1");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1]);
+
+        // Open-ended ranges
+        let res = parse_pos("3-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![2..usize::MAX]);
+
+        let res = parse_pos("-3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..3]);
+
+        let res = parse_pos("-2,4-");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..2, 3..usize::MAX]);
+
+        // Blanks separate ranges like commas
+        let res = parse_pos(" 1, 3-4 ");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1, 2..4]);
+
+        let res = parse_pos("1 3");
+        assert!(res.is_ok());
+        assert_eq!(res.unwrap(), vec![0..1, 2..3]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalized = |list| normalize(&parse_pos(list).unwrap());
+        assert_eq!(normalized("3,1"), vec![0..1, 2..3]);
+        assert_eq!(normalized("1,1"), vec![0..1]);
+        assert_eq!(normalized("1-2,2-4,5"), vec![0..5]);
+        assert_eq!(normalized("4-,-5"), vec![0..usize::MAX]);
+    }
+
+    #[test]
+    fn test_invert() {
+        let inverted = |list| invert(&parse_pos(list).unwrap());
+        assert_eq!(inverted("2"), vec![0..1, 2..usize::MAX]);
+        assert_eq!(inverted("1,4-5,2"), vec![2..3, 5..usize::MAX]);
+        assert_eq!(inverted("3-"), vec![0..2]);
+        assert!(inverted("-2,3-").is_empty());
     }
 
     #[test]
@@ -340,6 +685,43 @@ mod unit_tests {
         assert_eq!(extract_fields(&rec, &[0..1, 2..3]), &["Captain", "12345"]);
         assert_eq!(extract_fields(&rec, &[0..1, 3..4]), &["Captain"]);
         assert_eq!(extract_fields(&rec, &[1..2, 0..1]), &["Sham", "Captain"]);
+        assert_eq!(
+            extract_fields(&rec, &[2..3, 1..usize::MAX]),
+            &["12345", "Sham", "12345"]
+        );
+        assert_eq!(extract_fields(&rec, &[5..usize::MAX, 0..1]), &["Captain"]);
+    }
+
+    #[test]
+    fn test_write_fields() {
+        let written_as = |fields: &[&str], delimiter, quote_style| {
+            let mut out = vec![];
+            write_fields(&mut out, fields, delimiter, quote_style).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let written =
+            |fields: &[&str], delimiter| written_as(fields, delimiter, QuoteStyle::Necessary);
+        assert_eq!(written(&["a", "b"], ","), "a,b\n");
+        assert_eq!(written(&["a", "b"], " | "), "a | b\n");
+        assert_eq!(written(&["a,b", "c"], ","), "\"a,b\",c\n");
+        assert_eq!(written(&["a,b", "c"], "\t"), "a,b\tc\n");
+        assert_eq!(written(&["say \"hi\"", "x"], ":"), "\"say \"\"hi\"\"\":x\n");
+        assert_eq!(written(&["a", "b"], ""), "ab\n");
+        assert_eq!(written(&[], ","), "\n");
+
+        let fields = ["a", "1.5", "-2", "x,y"];
+        assert_eq!(
+            written_as(&fields, ",", QuoteStyle::Always),
+            "\"a\",\"1.5\",\"-2\",\"x,y\"\n"
+        );
+        assert_eq!(
+            written_as(&fields, ",", QuoteStyle::NonNumeric),
+            "\"a\",1.5,-2,\"x,y\"\n"
+        );
+        assert_eq!(
+            written_as(&fields, ",", QuoteStyle::Never),
+            "a,1.5,-2,x,y\n"
+        );
     }
 
     #[test]
@@ -350,6 +732,8 @@ mod unit_tests {
         assert_eq!(extract_chars("ábc", &[0..3]), "ábc".to_string());
         assert_eq!(extract_chars("ábc", &[2..3, 1..2]), "cb".to_string());
         assert_eq!(extract_chars("ábc", &[0..1, 1..2, 4..5]), "áb".to_string());
+        assert_eq!(extract_chars("ábc", &[2..3, 1..usize::MAX]), "cbc".to_string());
+        assert_eq!(extract_chars("ábc", &[0..1, 0..usize::MAX]), "áábc".to_string());
     }
 
     #[test]
@@ -360,5 +744,6 @@ mod unit_tests {
         assert_eq!(extract_bytes("ábc", &[0..4]), "ábc".to_string());
         assert_eq!(extract_bytes("ábc", &[3..4, 2..3]), "cb".to_string());
         assert_eq!(extract_bytes("ábc", &[0..2, 5..6]), "á".to_string());
+        assert_eq!(extract_bytes("ábc", &[0..2, 3..usize::MAX]), "ác".to_string());
     }
 }
@@ -9,6 +9,7 @@ const PRG: &str = "cutr";
 const CSV: &str = "tests/inputs/movies1.csv";
 const TSV: &str = "tests/inputs/movies1.tsv";
 const BOOKS: &str = "tests/inputs/books.tsv";
+const QUOTED: &str = "tests/inputs/movies2.csv";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -337,3 +338,197 @@ fn tsv_c1_8() -> TestResult {
 fn repeated_value() -> TestResult {
     run(&[BOOKS, "-c", "1,1"], "tests/expected/books.c1,1.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_open() -> TestResult {
+    run(&[TSV, "-f", "2-"], "tests/expected/movies1.tsv.f2-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_c_open_3() -> TestResult {
+    run(&[TSV, "-c", "-3"], "tests/expected/movies1.tsv.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn books_b8_open() -> TestResult {
+    run_lossy(&[BOOKS, "-b", "8-"], "tests/expected/books.b8-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_blank() -> TestResult {
+    run(&[TSV, "-f", "1, 3"], "tests/expected/movies1.tsv.f1,3.out")?;
+    run(&[TSV, "-f", "1 3"], "tests/expected/movies1.tsv.f1,3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f2_2() -> TestResult {
+    run(&[TSV, "-f", "2-2"], "tests/expected/movies1.tsv.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_complement_f2() -> TestResult {
+    run(
+        &[TSV, "--complement", "-f", "2"],
+        "tests/expected/movies1.tsv.complement.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_complement_f1_3() -> TestResult {
+    run(
+        &[TSV, "--complement", "-f", "3,1"],
+        "tests/expected/movies1.tsv.complement.f1,3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_complement_c2_open() -> TestResult {
+    run(
+        &[TSV, "--complement", "-c", "2-"],
+        "tests/expected/movies1.tsv.complement.c2-.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_complement_f2() -> TestResult {
+    run(
+        &[CSV, "--complement", "-f", "2", "-d", ","],
+        "tests/expected/movies1.csv.complement.f2.dcomma.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn undelimited_f2() -> TestResult {
+    run(
+        &["tests/inputs/undelimited.tsv", "-f", "2"],
+        "tests/expected/undelimited.tsv.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn undelimited_f2_only_delimited() -> TestResult {
+    run(
+        &["tests/inputs/undelimited.tsv", "-f", "2", "-s"],
+        "tests/expected/undelimited.tsv.f2.s.out",
+    )?;
+    run(
+        &["tests/inputs/undelimited.tsv", "-f", "2", "--only-delimited"],
+        "tests/expected/undelimited.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn blank_lines_f2() -> TestResult {
+    // Undelimited lines are printed as they are, without CSV quoting, and
+    // empty lines are kept.
+    run(
+        &["tests/inputs/blank_lines.tsv", "-f", "2"],
+        "tests/expected/blank_lines.tsv.f2.out",
+    )?;
+    run(
+        &["tests/inputs/blank_lines.tsv", "-f", "2", "--mode", "plain"],
+        "tests/expected/blank_lines.tsv.f2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn blank_lines_f2_only_delimited() -> TestResult {
+    run(
+        &["tests/inputs/blank_lines.tsv", "-f", "2", "-s"],
+        "tests/expected/blank_lines.tsv.f2.s.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn tsv_f1_3_output_delimiter() -> TestResult {
+    run(
+        &[TSV, "-f", "1,3", "--output-delimiter", " | "],
+        "tests/expected/movies1.tsv.f1,3.output_delimiter.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_f1_2_output_delimiter() -> TestResult {
+    run(
+        &[CSV, "-f", "1-2", "-d", ",", "--output-delimiter", ";"],
+        "tests/expected/movies1.csv.f1-2.dcomma.output_delimiter.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_only_delimited_bytes() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-b", "1", "-s"])
+        .assert()
+        .failure();
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-c", "1", "--output-delimiter", ":"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_mode_plain() -> TestResult {
+    run(
+        &[QUOTED, "-d", ",", "-f", "1-2", "--mode", "plain"],
+        "tests/expected/movies2.csv.f1-2.plain.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_mode_csv() -> TestResult {
+    run(
+        &[QUOTED, "-d", ",", "-f", "1-2"],
+        "tests/expected/movies2.csv.f1-2.out",
+    )?;
+    run(
+        &[QUOTED, "-d", ",", "-f", "1-2", "--mode=csv"],
+        "tests/expected/movies2.csv.f1-2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn csv_quote_style() -> TestResult {
+    run(
+        &[QUOTED, "-d", ",", "-f", "1-2", "--quote", "always"],
+        "tests/expected/movies2.csv.f1-2.quote_always.out",
+    )?;
+    run(
+        &[QUOTED, "-d", ",", "-f", "1-2", "--quote=non-numeric"],
+        "tests/expected/movies2.csv.f1-2.quote_non_numeric.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_quote_plain_mode() -> TestResult {
+    dies(
+        &[CSV, "-f", "1", "--mode", "plain", "--quote", "always"],
+        "--quote cannot be used with --mode=plain",
+    )?;
+    Command::cargo_bin(PRG)?
+        .args([CSV, "-f", "1", "--mode", "tsv"])
+        .assert()
+        .failure();
+    Ok(())
+}
@@ -0,0 +1,123 @@
+// ファイルの内容を見る -contains と -mime。
+use regex::bytes::Regex;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+// 種類の判定に読む先頭のバイト数
+const SNIFF_LEN: usize = 512;
+
+// 先頭のバイト列と種類。上から順に調べる。
+const MAGIC: [(&[u8], &str); 14] = [
+    (b"\x7fELF", "application/x-executable"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\0", "application/x-xz"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"ID3", "audio/mpeg"),
+    (b"OggS", "audio/ogg"),
+    (b"\0asm", "application/wasm"),
+];
+
+// 1行ずつ読み、いずれかの行が一致した時点でやめる。
+pub fn contains(path: &Path, re: &Regex) -> io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(false);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if re.is_match(&line) {
+            return Ok(true);
+        }
+    }
+}
+
+pub fn mime_type(path: &Path) -> io::Result<&'static str> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)?;
+    Ok(sniff(&buf))
+}
+
+// 既知のバイト列で始まらなければ、UTF-8 (BOM付きのUTF-16も) として読めるものをテキストとする。
+fn sniff(buf: &[u8]) -> &'static str {
+    if buf.is_empty() {
+        return "inode/x-empty";
+    }
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| buf.starts_with(magic)) {
+        return mime;
+    }
+    if buf.len() >= 12 && &buf[..4] == b"RIFF" && &buf[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+    if buf.starts_with(b"\xff\xfe") || buf.starts_with(b"\xfe\xff") {
+        return "text/plain";
+    }
+    let text = match std::str::from_utf8(buf) {
+        Ok(text) => text,
+        // 読んだ範囲の末尾で切れた文字は許す。
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return "application/octet-stream",
+    };
+    match text.contains('\0') {
+        true => "application/octet-stream",
+        false => "text/plain",
+    }
+}
+
+// TYPE は `image/png`、`image/*` または `image` の形で、大文字と小文字を区別しない。
+pub fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let (kind, _) = mime.split_once('/').unwrap_or((mime, ""));
+    match pattern.split_once('/') {
+        Some((pattern, "*")) => pattern.eq_ignore_ascii_case(kind),
+        Some(_) => pattern.eq_ignore_ascii_case(mime),
+        None => pattern.eq_ignore_ascii_case(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mime_matches, sniff};
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b""), "inode/x-empty");
+        assert_eq!(sniff(b"\x7fELF\x02\x01\x01\0"), "application/x-executable");
+        assert_eq!(sniff(b"\x1f\x8b\x08\0"), "application/gzip");
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt "), "application/octet-stream");
+        assert_eq!(sniff("a,b\nこんにちは\n".as_bytes()), "text/plain");
+        assert_eq!(sniff(&"あ".as_bytes()[..2]), "text/plain");
+        assert_eq!(sniff(b"\xff\xfea\0b\0"), "text/plain");
+        assert_eq!(sniff(b"a\0b"), "application/octet-stream");
+        assert_eq!(sniff(b"\xc3\x28"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_mime_matches() {
+        assert!(mime_matches("image/png", "image/png"));
+        assert!(mime_matches("IMAGE/PNG", "image/png"));
+        assert!(mime_matches("image/*", "image/png"));
+        assert!(mime_matches("image", "image/gif"));
+        assert!(!mime_matches("image/png", "image/gif"));
+        assert!(!mime_matches("image", "application/gzip"));
+        assert!(!mime_matches("application/gz", "application/gzip"));
+    }
+}
@@ -0,0 +1,281 @@
+// --duplicatesで、式に一致した通常のファイルから内容が同じものを探す。
+use crate::{sys, walk::Entry};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashSet},
+    fs::File,
+    hash::Hasher,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+// 最初に比べる先頭部分のバイト数
+const PARTIAL_LEN: u64 = 4096;
+
+// (デバイス, inode)。unixでだけ使う。
+type Inode = Option<(u64, u64)>;
+
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    files: Vec<(u64, Inode, PathBuf)>,
+}
+
+impl Duplicates {
+    // 空のファイルはすべて同じ内容になるので数えない。
+    pub fn add(&mut self, entry: &Entry) {
+        if !entry.file_type().is_file() {
+            return;
+        }
+        match entry.metadata() {
+            Ok(metadata) if metadata.len() > 0 => self.files.push((
+                metadata.len(),
+                cfg!(unix).then(|| (sys::dev(&metadata), sys::ino(&metadata))),
+                entry.path().to_path_buf(),
+            )),
+            Ok(_) => {}
+            Err(e) => eprintln!("{}: {}", entry.path().display(), e),
+        }
+    }
+
+    // 大きさ、先頭部分のハッシュ、全体のハッシュの順に絞り込み、最後に内容をバイトごとに確かめる。
+    // ハードリンクは同じファイルなので、パスの順で最初のものだけを残す。
+    // 大きい順、同じ大きさなら最初のパスの順に並べる。
+    pub fn groups(mut self) -> Vec<(u64, Vec<PathBuf>)> {
+        self.files.sort_by(|(_, _, a), (_, _, b)| a.cmp(b));
+        let mut seen = HashSet::new();
+        let mut by_size: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+        for (size, inode, path) in self.files {
+            if inode.is_none_or(|inode| seen.insert(inode)) {
+                by_size.entry(size).or_default().push(path);
+            }
+        }
+        let mut groups = vec![];
+        for (size, mut paths) in by_size {
+            paths.dedup();
+            for group in split(paths, |path| hash(path, PARTIAL_LEN)) {
+                // 先頭部分がファイル全体なら読み直すまでもない。
+                let group = match size <= PARTIAL_LEN {
+                    true => vec![group],
+                    false => split(group, |path| hash(path, u64::MAX)),
+                };
+                for group in group {
+                    groups.extend(same_contents(group).into_iter().map(|paths| (size, paths)));
+                }
+            }
+        }
+        groups.sort_by(|(a_size, a), (b_size, b)| b_size.cmp(a_size).then_with(|| a.cmp(b)));
+        groups
+    }
+
+    // テキストではfdupesと同じく、1行に1つのパスを並べ、グループの間に空行を入れる。
+    pub fn write(self, json: bool, out: &mut impl Write) -> io::Result<()> {
+        let groups = self.groups();
+        if json {
+            return write_json(&groups, out);
+        }
+        for (i, (_, paths)) in groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            for path in paths {
+                writeln!(out, "{}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// keyが同じものに分け、2つ以上あるものだけを残す。読めないファイルは警告して除く。
+fn split<K, F>(paths: Vec<PathBuf>, key: F) -> Vec<Vec<PathBuf>>
+where
+    K: Ord,
+    F: Fn(&Path) -> io::Result<K>,
+{
+    if paths.len() < 2 {
+        return vec![];
+    }
+    let mut buckets: BTreeMap<K, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        match key(&path) {
+            Ok(key) => buckets.entry(key).or_default().push(path),
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    buckets
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
+// 先頭のlimitバイトのハッシュ。衝突しにくいよう、種を変えた2つを合わせて128ビットにする。
+fn hash(path: &Path, limit: u64) -> io::Result<(u64, u64)> {
+    let mut file = File::open(path)?.take(limit);
+    let mut hashers = (DefaultHasher::new(), DefaultHasher::new());
+    hashers.1.write_u8(1);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match read_full(&mut file, &mut buf)? {
+            0 => return Ok((hashers.0.finish(), hashers.1.finish())),
+            len => {
+                hashers.0.write(&buf[..len]);
+                hashers.1.write(&buf[..len]);
+            }
+        }
+    }
+}
+
+// ハッシュが同じものを、内容がバイトごとに等しいものに分け、2つ以上あるものだけを残す。
+// ふつうはすべて最初のパスと等しいので、グループが分かれることはほとんどない。
+// 読めなくなったファイルは警告して除く。
+fn same_contents(paths: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut groups: Vec<Vec<PathBuf>> = vec![];
+    'paths: for path in paths {
+        let mut i = 0;
+        while i < groups.len() {
+            match equal(&groups[i][0], &path) {
+                Ok(true) => {
+                    groups[i].push(path);
+                    continue 'paths;
+                }
+                Ok(false) => i += 1,
+                Err((failed, e)) => {
+                    eprintln!("{}: {}", failed.display(), e);
+                    if failed == path {
+                        continue 'paths;
+                    }
+                    // 比べる相手のほうが読めなければ、それを除いて比べ直す。
+                    groups[i].remove(0);
+                    if groups[i].is_empty() {
+                        groups.remove(i);
+                    }
+                }
+            }
+        }
+        groups.push(vec![path]);
+    }
+    groups.retain(|paths| paths.len() > 1);
+    groups
+}
+
+// 読めなかった場合は、そのファイルのパスとエラーを返す。
+fn equal<'a>(a: &'a Path, b: &'a Path) -> Result<bool, (&'a Path, io::Error)> {
+    let open = |path| File::open(path).map_err(|e| (path, e));
+    let (mut a_file, mut b_file) = (open(a)?, open(b)?);
+    let (mut a_buf, mut b_buf) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let len = read_full(&mut a_file, &mut a_buf).map_err(|e| (a, e))?;
+        let b_len = read_full(&mut b_file, &mut b_buf).map_err(|e| (b, e))?;
+        if len != b_len || a_buf[..len] != b_buf[..len] {
+            return Ok(false);
+        }
+        if len == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+// bufが埋まるか終わりに達するまで読み、読んだバイト数を返す。
+fn read_full(file: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+// [{"size": N, "paths": [...]}, ...] の形で、1グループを1行に書く。
+fn write_json(groups: &[(u64, Vec<PathBuf>)], out: &mut impl Write) -> io::Result<()> {
+    if groups.is_empty() {
+        return writeln!(out, "[]");
+    }
+    writeln!(out, "[")?;
+    for (i, (size, paths)) in groups.iter().enumerate() {
+        let paths: Vec<String> = paths
+            .iter()
+            .map(|path| json_string(&path.to_string_lossy()))
+            .collect();
+        let comma = if i + 1 < groups.len() { "," } else { "" };
+        writeln!(
+            out,
+            "  {{\"size\": {}, \"paths\": [{}]}}{}",
+            size,
+            paths.join(", "),
+            comma
+        )?;
+    }
+    writeln!(out, "]")
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{json_string, same_contents, write_json};
+    use std::{env, fs, path::PathBuf};
+
+    #[test]
+    fn test_same_contents() {
+        let dir = env::temp_dir().join(format!("findr_same_contents_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in [("a", "xy"), ("b", "xy"), ("c", "xz"), ("d", "xz")] {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let paths = |names: &[&str]| names.iter().map(|name| dir.join(name)).collect::<Vec<_>>();
+
+        assert_eq!(
+            same_contents(paths(&["a", "c", "b", "d"])),
+            vec![paths(&["a", "b"]), paths(&["c", "d"])]
+        );
+        // 先頭のファイルが読めなくても、残りを比べる
+        assert_eq!(
+            same_contents(paths(&["missing", "a", "b"])),
+            vec![paths(&["a", "b"])]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a/b.txt"), "\"a/b.txt\"");
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(json_string("a\nb\x01"), "\"a\\nb\\u0001\"");
+    }
+
+    #[test]
+    fn test_write_json() {
+        let mut out = vec![];
+        write_json(&[], &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[]\n");
+
+        let groups = vec![
+            (3, vec![PathBuf::from("a"), PathBuf::from("b")]),
+            (1, vec![PathBuf::from("c"), PathBuf::from("d")]),
+        ];
+        let mut out = vec![];
+        write_json(&groups, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[\n  {\"size\": 3, \"paths\": [\"a\", \"b\"]},\n  {\"size\": 1, \"paths\": [\"c\", \"d\"]}\n]\n"
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{sys, walk::Entry, MyResult};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    io::{self, BufRead},
+    mem,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+// sysconfで分からないときは、xargsの既定値にならいこの範囲に収める。
+const ARG_MAX: usize = 128 * 1024;
+
+// -exec, -execdir, -ok, -okdir
+#[derive(Debug)]
+pub struct Exec {
+    name: String,
+    command: Vec<String>,
+    batch: bool,
+    in_dir: bool,
+    prompt: bool,
+    // `+` で溜める引数に使える大きさ。ARG_MAXから環境変数とコマンドの分を除いたもの。
+    max_size: usize,
+    pending: Mutex<Batch>,
+    failed: AtomicBool,
+}
+
+// `+` で溜めている引数と、-execdirの場合はその実行ディレクトリ。
+#[derive(Debug, Default)]
+struct Batch {
+    dir: Option<PathBuf>,
+    paths: Vec<OsString>,
+    size: usize,
+}
+
+impl Exec {
+    // `;` まで、または `{}` の直後の `+` までをコマンドとして読む。
+    // 読み込んだ引数の数とともに返す。
+    pub fn parse(name: &str, args: &[String]) -> MyResult<(Exec, usize)> {
+        let end = args
+            .iter()
+            .enumerate()
+            .position(|(i, arg)| arg == ";" || (arg == "+" && i > 0 && args[i - 1] == "{}"))
+            .ok_or_else(|| format!("missing argument to \"{}\"", name))?;
+        let prompt = name.starts_with("-ok");
+        let batch = args[end] == "+";
+        if end == 0 {
+            return Err(From::from(format!("missing argument to \"{}\"", name)));
+        }
+        if batch && prompt {
+            return Err(From::from(format!("\"{}\" does not support \"+\"", name)));
+        }
+        if batch && args[..end - 1].iter().any(|arg| arg.contains("{}")) {
+            return Err(From::from(format!(
+                "only one instance of {{}} is supported with \"{} ... +\"",
+                name
+            )));
+        }
+        let command = args[..if batch { end - 1 } else { end }].to_vec();
+        let base_size: usize = env::vars_os()
+            .map(|(key, val)| key.len() + val.len() + 2)
+            .chain(command.iter().map(|arg| arg.len() + 1))
+            .map(|size| size + mem::size_of::<usize>())
+            .sum();
+        Ok((
+            Exec {
+                name: name.to_string(),
+                command,
+                batch,
+                in_dir: name.ends_with("dir"),
+                prompt,
+                max_size: sys::arg_max().unwrap_or(ARG_MAX).saturating_sub(base_size),
+                pending: Mutex::new(Batch::default()),
+                failed: AtomicBool::new(false),
+            },
+            end + 1,
+        ))
+    }
+
+    pub fn eval(&self, entry: &Entry, out: &mut Vec<u8>) -> bool {
+        let (dir, path) = self.target(entry.path());
+        if !self.batch {
+            // コマンドの出力も-printと同じくoutに溜め、--sortedや並列走査での順序を保つ。
+            let args = self.command.iter().map(|arg| replace_braces(arg, &path));
+            return self.execute(dir.as_deref(), args.collect(), Some(out));
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        let size = path.len() + 1 + mem::size_of::<usize>();
+        if !pending.paths.is_empty() && (pending.dir != dir || pending.size + size > self.max_size)
+        {
+            self.flush_batch(&mut pending);
+        }
+        pending.dir = dir;
+        pending.paths.push(path);
+        pending.size += size;
+        true
+    }
+
+    // 溜まっている `+` の引数を実行し、失敗したコマンドがあったかを返す。
+    pub fn finish(&self) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if !pending.paths.is_empty() {
+            self.flush_batch(&mut pending);
+        }
+        self.failed.load(Ordering::SeqCst)
+    }
+
+    fn flush_batch(&self, pending: &mut Batch) {
+        let batch = mem::take(pending);
+        let args = self
+            .command
+            .iter()
+            .map(OsString::from)
+            .chain(batch.paths)
+            .collect();
+        self.execute(batch.dir.as_deref(), args, None);
+    }
+
+    // -execdirではエントリのディレクトリで ./名前 を対象にする。
+    fn target(&self, path: &Path) -> (Option<PathBuf>, OsString) {
+        if !self.in_dir {
+            return (None, path.as_os_str().to_os_string());
+        }
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        (Some(dir), Path::new(".").join(name).into_os_string())
+    }
+
+    // outがあればコマンドの標準出力をそこに書き、なければそのまま端末などに出す。
+    fn execute(&self, dir: Option<&Path>, args: Vec<OsString>, out: Option<&mut Vec<u8>>) -> bool {
+        if self.prompt && !confirm(&args) {
+            return false;
+        }
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]);
+        if let Some(dir) = dir {
+            command.current_dir(dir);
+        }
+        if out.is_some() {
+            command.stdout(Stdio::piped());
+        }
+        let status = command
+            .spawn()
+            .and_then(|child| child.wait_with_output())
+            .map(|output| {
+                if let Some(out) = out {
+                    out.extend_from_slice(&output.stdout);
+                }
+                output.status
+            });
+        match status {
+            Ok(status) if status.success() => true,
+            Ok(_) => {
+                self.failed.store(true, Ordering::SeqCst);
+                false
+            }
+            Err(e) => {
+                eprintln!("{}: {}: {}", self.name, args[0].to_string_lossy(), e);
+                self.failed.store(true, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+}
+
+// {} をパスに置き換える。UTF-8でないパスも壊さないよう、OsStringのまま組み立てる。
+fn replace_braces(arg: &str, path: &OsStr) -> OsString {
+    let mut replaced = OsString::new();
+    for (i, part) in arg.split("{}").enumerate() {
+        if i > 0 {
+            replaced.push(path);
+        }
+        replaced.push(part);
+    }
+    replaced
+}
+
+// 並列走査で問い合わせが混ざらないよう、1つずつ尋ねる。
+static PROMPT: Mutex<()> = Mutex::new(());
+
+fn confirm(args: &[OsString]) -> bool {
+    let _prompt = PROMPT.lock().unwrap();
+    let command = args
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    eprint!("< {} > ? ", command);
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).is_ok()
+        && matches!(answer.trim(), "y" | "Y" | "yes" | "Yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_braces, Exec};
+    use std::ffi::OsStr;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let (exec, len) = Exec::parse("-exec", &args("echo {} ; -print")).unwrap();
+        assert_eq!(exec.command, vec!["echo", "{}"]);
+        assert!(!exec.batch);
+        assert_eq!(len, 3);
+
+        let (exec, len) = Exec::parse("-execdir", &args("ls -l {} +")).unwrap();
+        assert_eq!(exec.command, vec!["ls", "-l"]);
+        assert!(exec.batch && exec.in_dir);
+        assert_eq!(len, 4);
+
+        // {} の直後でなければ + は引数として扱う
+        let (exec, _) = Exec::parse("-exec", &args("expr 1 + 1 ;")).unwrap();
+        assert_eq!(exec.command, vec!["expr", "1", "+", "1"]);
+
+        let res = Exec::parse("-exec", &args("echo {}"));
+        assert_eq!(res.unwrap_err().to_string(), "missing argument to \"-exec\"");
+
+        let res = Exec::parse("-exec", &args(";"));
+        assert_eq!(res.unwrap_err().to_string(), "missing argument to \"-exec\"");
+
+        let res = Exec::parse("-ok", &args("rm {} +"));
+        assert_eq!(res.unwrap_err().to_string(), "\"-ok\" does not support \"+\"");
+
+        let res = Exec::parse("-exec", &args("cp {} {} +"));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_replace_braces() {
+        let path = OsStr::new("a b.txt");
+        assert_eq!(replace_braces("{}", path), "a b.txt");
+        assert_eq!(replace_braces("x{}y{}", path), "xa b.txtya b.txt");
+        assert_eq!(replace_braces("echo", path), "echo");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let path = OsStr::from_bytes(b"\xff.txt");
+            assert_eq!(replace_braces("file:{}", path).as_bytes(), b"file:\xff.txt");
+        }
+    }
+}
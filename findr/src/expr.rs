@@ -0,0 +1,923 @@
+use crate::{
+    content,
+    exec::Exec,
+    format::{self, Format},
+    sys,
+    walk::Entry,
+    EntryType, MyResult,
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{bytes, Regex, RegexBuilder};
+use std::{
+    fs::{self, FileType, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+// find形式の式。-o より -a (省略可) が、-a より ! が優先される。
+#[derive(Debug)]
+pub enum Expr {
+    True,
+    False,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Name(GlobMatcher),
+    Path(GlobMatcher),
+    Regex(Regex),
+    NameRegex(Regex),
+    Type(Vec<EntryType>),
+    XType(Vec<EntryType>),
+    Size(Compare, u64),
+    Time(TimeKind, Compare, u64, SystemTime),
+    Newer(SystemTime),
+    Empty,
+    Perm(Perm, u32),
+    Uid(Compare),
+    Gid(Compare),
+    NoUser,
+    NoGroup,
+    Inum(Compare),
+    Links(Compare),
+    SameFile(u64, u64),
+    Contains(bytes::Regex),
+    Mime(String),
+    Print,
+    Print0,
+    Printf(Format),
+    Ls(SystemTime),
+    Exec(Exec),
+    Prune,
+    Delete,
+    XDev,
+}
+
+// 1エントリの評価中にアクションが残す結果。
+#[derive(Debug, Default)]
+pub struct Context {
+    pub out: Vec<u8>,
+    pub prune: bool,
+    pub delete: Vec<PathBuf>,
+}
+
+// +N は N より大きい、-N は N より小さい、N はちょうど N。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Compare {
+    Less(u64),
+    Equal(u64),
+    Greater(u64),
+}
+
+impl Compare {
+    fn parse(val: &str) -> Option<Compare> {
+        let (cmp, num): (fn(u64) -> Compare, _) = match val.as_bytes().first() {
+            Some(b'+') => (Compare::Greater, &val[1..]),
+            Some(b'-') => (Compare::Less, &val[1..]),
+            _ => (Compare::Equal, val),
+        };
+        if num.is_empty() || !num.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        num.parse().ok().map(cmp)
+    }
+
+    fn matches(&self, val: u64) -> bool {
+        match *self {
+            Compare::Less(n) => val < n,
+            Compare::Equal(n) => val == n,
+            Compare::Greater(n) => val > n,
+        }
+    }
+}
+
+// -perm MODE はちょうど一致、-MODE はすべてのビット、/MODE はいずれかのビットが立っている。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Perm {
+    Exact,
+    All,
+    Any,
+}
+
+impl Perm {
+    fn matches(&self, bits: u32, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match self {
+            Perm::Exact => mode == bits,
+            Perm::All => mode & bits == bits,
+            Perm::Any => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimeKind {
+    Accessed,
+    Changed,
+    Modified,
+}
+
+impl TimeKind {
+    pub fn get(&self, metadata: &Metadata) -> io::Result<SystemTime> {
+        match self {
+            TimeKind::Accessed => metadata.accessed(),
+            TimeKind::Modified => metadata.modified(),
+            TimeKind::Changed => changed(metadata),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn changed(metadata: &Metadata) -> io::Result<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let ctime = Duration::new(metadata.ctime() as u64, metadata.ctime_nsec() as u32);
+    Ok(SystemTime::UNIX_EPOCH + ctime)
+}
+
+// ctimeのないプラットフォームでは更新日時で代用する。
+#[cfg(not(unix))]
+fn changed(metadata: &Metadata) -> io::Result<SystemTime> {
+    metadata.modified()
+}
+
+impl Expr {
+    pub fn and(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::True, expr) | (expr, Expr::True) => expr,
+            (lhs, rhs) => Expr::And(Box::new(lhs), Box::new(rhs)),
+        }
+    }
+
+    pub fn or(self, other: Expr) -> Expr {
+        Expr::Or(Box::new(self), Box::new(other))
+    }
+
+    // -printなどのアクションを含むか。含まなければ暗黙の-printを補う。
+    pub fn has_action(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.has_action(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_action() || rhs.has_action(),
+            Expr::Print
+            | Expr::Print0
+            | Expr::Printf(_)
+            | Expr::Ls(_)
+            | Expr::Exec(_)
+            | Expr::Delete => true,
+            _ => false,
+        }
+    }
+
+    pub fn has_delete(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.has_delete(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_delete() || rhs.has_delete(),
+            Expr::Delete => true,
+            _ => false,
+        }
+    }
+
+    // -trueやアクションのように、エントリを選ばないもの以外を含むか。
+    fn has_test(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.has_test(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.has_test() || rhs.has_test(),
+            Expr::True
+            | Expr::Print
+            | Expr::Print0
+            | Expr::Printf(_)
+            | Expr::Ls(_)
+            | Expr::Prune
+            | Expr::Delete
+            | Expr::XDev => false,
+            _ => true,
+        }
+    }
+
+    // すべてを削除しないよう、テストを通る前に評価される-deleteを拒む。
+    pub fn check_delete(&self) -> MyResult<()> {
+        match self.delete_is_tested(false) {
+            true => Ok(()),
+            false => Err(From::from(
+                "\"-delete\" must follow the tests that select what to delete",
+            )),
+        }
+    }
+
+    // testedはこの式より前にテストが評価されているか。
+    fn delete_is_tested(&self, tested: bool) -> bool {
+        match self {
+            Expr::Not(expr) => expr.delete_is_tested(tested),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => {
+                lhs.delete_is_tested(tested) && rhs.delete_is_tested(tested || lhs.has_test())
+            }
+            Expr::Delete => tested,
+            _ => true,
+        }
+    }
+
+    // -xdev (-mount) は位置によらず走査全体に効く。
+    pub fn xdev(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.xdev(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.xdev() || rhs.xdev(),
+            Expr::XDev => true,
+            _ => false,
+        }
+    }
+
+    // 溜めている-execを実行し、失敗したコマンドがあったかを返す。
+    pub fn finish(&self) -> bool {
+        match self {
+            Expr::Not(expr) => expr.finish(),
+            Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => lhs.finish() | rhs.finish(),
+            Expr::Exec(exec) => exec.finish(),
+            _ => false,
+        }
+    }
+
+    pub fn eval(&self, entry: &Entry, ctx: &mut Context) -> bool {
+        match self {
+            Expr::True | Expr::XDev => true,
+            Expr::False => false,
+            Expr::Not(expr) => !expr.eval(entry, ctx),
+            Expr::And(lhs, rhs) => lhs.eval(entry, ctx) && rhs.eval(entry, ctx),
+            Expr::Or(lhs, rhs) => lhs.eval(entry, ctx) || rhs.eval(entry, ctx),
+            Expr::Name(glob) => glob.is_match(entry.file_name()),
+            Expr::Path(glob) => glob.is_match(entry.path()),
+            Expr::Regex(re) => re.is_match(&entry.path().to_string_lossy()),
+            Expr::NameRegex(re) => re.is_match(&entry.file_name().to_string_lossy()),
+            Expr::Type(entry_types) => entry_types
+                .iter()
+                .any(|entry_type| entry_type.matches(&entry.file_type())),
+            Expr::XType(entry_types) => {
+                let file_type = target_type(entry);
+                entry_types
+                    .iter()
+                    .any(|entry_type| entry_type.matches(&file_type))
+            }
+            Expr::Size(cmp, unit) => entry
+                .metadata()
+                .map(|metadata| cmp.matches(metadata.len().div_ceil(*unit)))
+                .unwrap_or(false),
+            Expr::Time(kind, cmp, unit, now) => entry
+                .metadata()
+                .and_then(|metadata| kind.get(&metadata))
+                .map(|time| {
+                    let age = now.duration_since(time).unwrap_or_default();
+                    cmp.matches(age.as_secs() / unit)
+                })
+                .unwrap_or(false),
+            Expr::Newer(reference) => entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .map(|time| time > *reference)
+                .unwrap_or(false),
+            Expr::Empty => entry
+                .metadata()
+                .map(|metadata| {
+                    if metadata.is_dir() {
+                        fs::read_dir(entry.path())
+                            .map(|mut entries| entries.next().is_none())
+                            .unwrap_or(false)
+                    } else {
+                        metadata.is_file() && metadata.len() == 0
+                    }
+                })
+                .unwrap_or(false),
+            Expr::Contains(re) => read_content(entry, |path| content::contains(path, re)),
+            Expr::Mime(pattern) => read_content(entry, |path| {
+                content::mime_type(path).map(|mime| content::mime_matches(pattern, mime))
+            }),
+            Expr::Perm(perm, bits) => entry
+                .metadata()
+                .map(|metadata| perm.matches(*bits, sys::mode(&metadata)))
+                .unwrap_or(false),
+            Expr::Uid(cmp) => entry
+                .metadata()
+                .map(|metadata| cmp.matches(sys::uid(&metadata).into()))
+                .unwrap_or(false),
+            Expr::Gid(cmp) => entry
+                .metadata()
+                .map(|metadata| cmp.matches(sys::gid(&metadata).into()))
+                .unwrap_or(false),
+            Expr::NoUser => entry
+                .metadata()
+                .map(|metadata| sys::user_name(sys::uid(&metadata)).is_none())
+                .unwrap_or(false),
+            Expr::NoGroup => entry
+                .metadata()
+                .map(|metadata| sys::group_name(sys::gid(&metadata)).is_none())
+                .unwrap_or(false),
+            Expr::Inum(cmp) => entry
+                .metadata()
+                .map(|metadata| cmp.matches(sys::ino(&metadata)))
+                .unwrap_or(false),
+            Expr::Links(cmp) => entry
+                .metadata()
+                .map(|metadata| cmp.matches(sys::nlink(&metadata)))
+                .unwrap_or(false),
+            Expr::SameFile(dev, ino) => entry
+                .metadata()
+                .map(|metadata| sys::dev(&metadata) == *dev && sys::ino(&metadata) == *ino)
+                .unwrap_or(false),
+            Expr::Print => {
+                writeln!(ctx.out, "{}", entry.path().display()).unwrap();
+                true
+            }
+            Expr::Print0 => {
+                write!(ctx.out, "{}\0", entry.path().display()).unwrap();
+                true
+            }
+            Expr::Printf(format) => {
+                format.write(entry, &mut ctx.out);
+                true
+            }
+            Expr::Ls(now) => {
+                format::write_ls(entry, *now, &mut ctx.out);
+                true
+            }
+            Expr::Exec(exec) => exec.eval(entry, &mut ctx.out),
+            Expr::Prune => {
+                ctx.prune = entry.file_type().is_dir();
+                true
+            }
+            // 削除は走査がすべて終わってから行う。
+            Expr::Delete => {
+                ctx.delete.push(entry.path().to_path_buf());
+                true
+            }
+        }
+    }
+}
+
+// 通常のファイルの内容を調べる。読めなければ警告して偽とする。
+fn read_content<F>(entry: &Entry, check: F) -> bool
+where
+    F: FnOnce(&Path) -> io::Result<bool>,
+{
+    if !entry.file_type().is_file() {
+        return false;
+    }
+    check(entry.path()).unwrap_or_else(|e| {
+        eprintln!("{}: {}", entry.path().display(), e);
+        false
+    })
+}
+
+// -xtypeで見る種類。リンクならリンク先の種類 (壊れていればリンク自体)、
+// -Lで辿ったリンクならリンク自体の種類になる。
+fn target_type(entry: &Entry) -> FileType {
+    let metadata = match (entry.file_type().is_symlink(), entry.path_is_symlink()) {
+        (true, _) => fs::metadata(entry.path()),
+        (false, true) => fs::symlink_metadata(entry.path()),
+        (false, false) => return entry.file_type(),
+    };
+    metadata.map_or(entry.file_type(), |metadata| metadata.file_type())
+}
+
+// 式で使える演算子、テスト、アクションの名前。
+const PRIMARIES: &str =
+    "( ! -not -o -or -a -and -true -false -name -iname -path -ipath -wholename -iwholename \
+    -regex -iregex -type -xtype -size -atime -ctime -mtime -amin -cmin -mmin -newer \
+    -empty -perm -user -group -uid -gid -inum -links -nouser -nogroup -samefile -contains \
+    -mime -print -print0 -printf -ls -prune -xdev -mount -delete -exec -execdir -ok \
+    -okdir";
+
+// get_argsでclapに渡す短いオプションの文字。
+const SHORT_OPTIONS: &str = "ntLHPj";
+
+// 式の始まりとみなす引数。これより前はパスやオプションとして扱う。
+// 名前が一致しない -xyz は、-tf や -j4 のように値を続けて書いた短いオプションでなければ
+// 式として解析し、知らない述語として報告する。
+pub fn is_expression_start(arg: &str) -> bool {
+    if PRIMARIES.split_whitespace().any(|name| name == arg) {
+        return true;
+    }
+    match arg.strip_prefix('-').and_then(|rest| rest.chars().next()) {
+        Some(c) => c != '-' && arg.len() > 2 && !SHORT_OPTIONS.contains(c),
+        None => false,
+    }
+}
+
+pub fn parse(args: &[String]) -> MyResult<Expr> {
+    if args.is_empty() {
+        return Ok(Expr::True);
+    }
+    let mut parser = Parser {
+        args,
+        pos: 0,
+        now: SystemTime::now(),
+    };
+    let expr = parser.parse_or()?;
+    if let Some(arg) = parser.peek() {
+        return Err(From::from(format!("unexpected \"{}\"", arg)));
+    }
+    Ok(expr)
+}
+
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+    now: SystemTime,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.args.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let arg = self.peek();
+        self.pos += 1;
+        arg
+    }
+
+    fn parse_or(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while let Some("-o" | "-or") = self.peek() {
+            self.next();
+            expr = expr.or(self.parse_and()?);
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                None | Some("-o" | "-or" | ")") => break,
+                Some("-a" | "-and") => {
+                    self.next();
+                }
+                _ => {}
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> MyResult<Expr> {
+        match self.peek() {
+            Some("!" | "-not") => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_not()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> MyResult<Expr> {
+        let prev = self.pos.checked_sub(1).map(|i| self.args[i].as_str());
+        let arg = match self.next() {
+            Some(arg) => arg,
+            None => {
+                return Err(From::from(match prev {
+                    Some(prev) => format!("expected an expression after \"{}\"", prev),
+                    None => "expected an expression".to_string(),
+                }))
+            }
+        };
+        match arg {
+            "(" => {
+                if self.peek() == Some(")") {
+                    return Err(From::from("empty parentheses are not allowed"));
+                }
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(From::from("missing \")\"")),
+                }
+            }
+            ")" => Err(From::from("unexpected \")\"")),
+            "-o" | "-or" | "-a" | "-and" => Err(From::from(format!(
+                "\"{}\" has no expression before it",
+                arg
+            ))),
+            "-true" => Ok(Expr::True),
+            "-false" => Ok(Expr::False),
+            "-name" | "-iname" => {
+                let val = self.value(arg)?;
+                Ok(Expr::Name(parse_glob(val, arg)?))
+            }
+            "-path" | "-ipath" | "-wholename" | "-iwholename" => {
+                let val = self.value(arg)?;
+                Ok(Expr::Path(parse_glob(val, arg)?))
+            }
+            "-regex" | "-iregex" => {
+                let val = self.value(arg)?;
+                Ok(Expr::Regex(parse_regex(val, arg)?))
+            }
+            "-type" | "-xtype" => {
+                let val = self.value(arg)?;
+                let entry_types = EntryType::parse_list(val)
+                    .ok_or_else(|| format!("invalid {} \"{}\"", arg, val))?;
+                Ok(match arg {
+                    "-type" => Expr::Type(entry_types),
+                    _ => Expr::XType(entry_types),
+                })
+            }
+            "-size" => {
+                let val = self.value(arg)?;
+                parse_size(val)
+                    .map(|(cmp, unit)| Expr::Size(cmp, unit))
+                    .ok_or_else(|| From::from(format!("invalid -size \"{}\"", val)))
+            }
+            "-atime" | "-ctime" | "-mtime" | "-amin" | "-cmin" | "-mmin" => {
+                let val = self.value(arg)?;
+                let cmp =
+                    Compare::parse(val).ok_or_else(|| format!("invalid {} \"{}\"", arg, val))?;
+                let kind = match &arg[1..2] {
+                    "a" => TimeKind::Accessed,
+                    "c" => TimeKind::Changed,
+                    _ => TimeKind::Modified,
+                };
+                let unit = if arg.ends_with("min") {
+                    60
+                } else {
+                    24 * 60 * 60
+                };
+                Ok(Expr::Time(kind, cmp, unit, self.now))
+            }
+            "-newer" => {
+                let val = self.value(arg)?;
+                let modified = fs::metadata(val)
+                    .and_then(|metadata| metadata.modified())
+                    .map_err(|e| format!("{}: {}", val, e))?;
+                Ok(Expr::Newer(modified))
+            }
+            "-empty" => Ok(Expr::Empty),
+            "-perm" => {
+                let val = self.value(arg)?;
+                parse_perm(val)
+                    .map(|(perm, bits)| Expr::Perm(perm, bits))
+                    .ok_or_else(|| From::from(format!("invalid -perm \"{}\"", val)))
+            }
+            "-user" => {
+                let val = self.value(arg)?;
+                sys::user_id(val)
+                    .or_else(|| val.parse().ok())
+                    .map(|uid| Expr::Uid(Compare::Equal(uid.into())))
+                    .ok_or_else(|| {
+                        From::from(format!("\"{}\" is not the name of a known user", val))
+                    })
+            }
+            "-group" => {
+                let val = self.value(arg)?;
+                sys::group_id(val)
+                    .or_else(|| val.parse().ok())
+                    .map(|gid| Expr::Gid(Compare::Equal(gid.into())))
+                    .ok_or_else(|| {
+                        From::from(format!("\"{}\" is not the name of a known group", val))
+                    })
+            }
+            "-uid" | "-gid" | "-inum" | "-links" => {
+                let val = self.value(arg)?;
+                let cmp =
+                    Compare::parse(val).ok_or_else(|| format!("invalid {} \"{}\"", arg, val))?;
+                Ok(match arg {
+                    "-uid" => Expr::Uid(cmp),
+                    "-gid" => Expr::Gid(cmp),
+                    "-inum" => Expr::Inum(cmp),
+                    _ => Expr::Links(cmp),
+                })
+            }
+            "-nouser" => Ok(Expr::NoUser),
+            "-nogroup" => Ok(Expr::NoGroup),
+            "-samefile" => {
+                if !cfg!(unix) {
+                    return Err(From::from(
+                        "\"-samefile\" is not supported on this platform",
+                    ));
+                }
+                let val = self.value(arg)?;
+                let metadata = fs::symlink_metadata(val).map_err(|e| format!("{}: {}", val, e))?;
+                Ok(Expr::SameFile(sys::dev(&metadata), sys::ino(&metadata)))
+            }
+            "-contains" => {
+                let val = self.value(arg)?;
+                bytes::Regex::new(val)
+                    .map(Expr::Contains)
+                    .map_err(|_| From::from(format!("invalid -contains \"{}\"", val)))
+            }
+            "-mime" => {
+                let val = self.value(arg)?;
+                Ok(Expr::Mime(val.to_string()))
+            }
+            "-print" => Ok(Expr::Print),
+            "-print0" => Ok(Expr::Print0),
+            "-printf" => {
+                let val = self.value(arg)?;
+                Ok(Expr::Printf(Format::parse(val)?))
+            }
+            "-ls" => Ok(Expr::Ls(self.now)),
+            "-prune" => Ok(Expr::Prune),
+            "-xdev" | "-mount" => Ok(Expr::XDev),
+            "-delete" => Ok(Expr::Delete),
+            "-exec" | "-execdir" | "-ok" | "-okdir" => {
+                let (exec, len) = Exec::parse(arg, &self.args[self.pos..])?;
+                self.pos += len;
+                Ok(Expr::Exec(exec))
+            }
+            _ if arg.starts_with('-') => Err(From::from(format!("unknown predicate \"{}\"", arg))),
+            _ => Err(From::from(format!(
+                "paths must precede the expression: \"{}\"",
+                arg
+            ))),
+        }
+    }
+
+    fn value(&mut self, name: &str) -> MyResult<&'a str> {
+        self.next()
+            .ok_or_else(|| From::from(format!("missing argument to \"{}\"", name)))
+    }
+}
+
+// 単位はfindと同じく c, w, b, k, M, G。省略時は512バイトのブロック。
+fn parse_size(val: &str) -> Option<(Compare, u64)> {
+    let (num, unit) = match val.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&val[..i], c),
+        _ => (val, 'b'),
+    };
+    let unit = match unit {
+        'c' => 1,
+        'w' => 2,
+        'b' => 512,
+        'k' => 1024,
+        'M' => 1024 * 1024,
+        'G' => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Compare::parse(num).map(|cmp| (cmp, unit))
+}
+
+// 8進数か、chmodと同じ u+w,g=rx のような記号で表したモード。
+// 先頭の - はすべてのビット、/ はいずれかのビットが立っていればよいことを表す。
+fn parse_perm(val: &str) -> Option<(Perm, u32)> {
+    let (perm, mode) = match val.as_bytes().first() {
+        Some(b'-') => (Perm::All, &val[1..]),
+        Some(b'/') => (Perm::Any, &val[1..]),
+        _ => (Perm::Exact, val),
+    };
+    if mode.is_empty() {
+        return None;
+    }
+    if mode.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|bits| *bits <= 0o7777)
+            .map(|bits| (perm, bits));
+    }
+    mode.split(',')
+        .try_fold(0, parse_symbolic_clause)
+        .map(|bits| (perm, bits))
+}
+
+// `[ugoa]*([-+=][rwxXst]*)+` の1節を bits に適用する。
+fn parse_symbolic_clause(mut bits: u32, clause: &str) -> Option<u32> {
+    let ops = clause.trim_start_matches(['u', 'g', 'o', 'a']);
+    let mut who = clause[..clause.len() - ops.len()]
+        .chars()
+        .map(|c| match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            _ => 0o7777,
+        })
+        .fold(0, |who, mask| who | mask);
+    if who == 0 {
+        who = 0o7777;
+    }
+
+    let mut chars = ops.chars().peekable();
+    chars.peek()?;
+    while let Some(op) = chars.next() {
+        let mut perm = 0;
+        while let Some(c) = chars.next_if(|c| !matches!(c, '+' | '-' | '=')) {
+            perm |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' | 'X' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => return None,
+            };
+        }
+        perm &= who;
+        match op {
+            '+' => bits |= perm,
+            '-' => bits &= !perm,
+            '=' => bits = (bits & !who) | perm,
+            _ => return None,
+        }
+    }
+    Some(bits)
+}
+
+// findと同じく * や ? は / にもマッチする。-i で始まる述語は大文字小文字を区別しない。
+fn parse_glob(val: &str, name: &str) -> MyResult<GlobMatcher> {
+    GlobBuilder::new(val)
+        .case_insensitive(name.starts_with("-i"))
+        .literal_separator(false)
+        .backslash_escape(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|_| From::from(format!("invalid {} \"{}\"", name, val)))
+}
+
+// -regexはパス全体にマッチする必要がある。
+fn parse_regex(val: &str, name: &str) -> MyResult<Regex> {
+    RegexBuilder::new(&format!("^(?:{})$", val))
+        .case_insensitive(name.starts_with("-i"))
+        .build()
+        .map_err(|_| From::from(format!("invalid {} \"{}\"", name, val)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_expression_start, parse, parse_glob, parse_perm, parse_regex, parse_size, Compare, Expr,
+        Perm,
+    };
+    use std::path::Path;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn parse_str(line: &str) -> String {
+        match parse(&args(line)) {
+            Ok(expr) => format!("{:?}", expr),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_expression_start() {
+        assert!(is_expression_start("("));
+        assert!(is_expression_start("!"));
+        assert!(is_expression_start("-name"));
+        assert!(is_expression_start("-o"));
+        assert!(!is_expression_start("-t"));
+        assert!(!is_expression_start("-j4"));
+        assert!(!is_expression_start("-tf"));
+        assert!(!is_expression_start("-ncsv"));
+        assert!(is_expression_start("-nouser"));
+        assert!(is_expression_start("-foo"));
+        assert!(!is_expression_start("--max-depth"));
+        assert!(!is_expression_start("tests/inputs"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10"), Some((Compare::Equal(10), 512)));
+        assert_eq!(parse_size("+10c"), Some((Compare::Greater(10), 1)));
+        assert_eq!(parse_size("-2k"), Some((Compare::Less(2), 1024)));
+        assert_eq!(parse_size("1M"), Some((Compare::Equal(1), 1024 * 1024)));
+        assert_eq!(
+            parse_size("+1G"),
+            Some((Compare::Greater(1), 1024 * 1024 * 1024))
+        );
+        assert_eq!(parse_size("3w"), Some((Compare::Equal(3), 2)));
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size("+-1"), None);
+        assert_eq!(parse_size("1x"), None);
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert_eq!(parse_perm("644"), Some((Perm::Exact, 0o644)));
+        assert_eq!(parse_perm("-2"), Some((Perm::All, 0o2)));
+        assert_eq!(parse_perm("/4000"), Some((Perm::Any, 0o4000)));
+        assert_eq!(parse_perm("u=rw,go=r"), Some((Perm::Exact, 0o644)));
+        assert_eq!(parse_perm("-o+w"), Some((Perm::All, 0o2)));
+        assert_eq!(parse_perm("/u+s,g+s"), Some((Perm::Any, 0o6000)));
+        assert_eq!(parse_perm("+x"), Some((Perm::Exact, 0o111)));
+        assert_eq!(parse_perm("a=rwx,o-w"), Some((Perm::Exact, 0o775)));
+        assert_eq!(parse_perm("-"), None);
+        assert_eq!(parse_perm("10000"), None);
+        assert_eq!(parse_perm("u"), None);
+        assert_eq!(parse_perm("u+q"), None);
+        assert_eq!(parse_perm("648"), None);
+
+        assert!(Perm::Exact.matches(0o644, 0o100644));
+        assert!(!Perm::Exact.matches(0o644, 0o100664));
+        assert!(Perm::All.matches(0o022, 0o100666));
+        assert!(!Perm::All.matches(0o022, 0o100646));
+        assert!(Perm::Any.matches(0o022, 0o100646));
+        assert!(!Perm::Any.matches(0o022, 0o100644));
+        assert!(Perm::Any.matches(0, 0o100644));
+    }
+
+    #[test]
+    fn test_parse_glob() {
+        let glob = parse_glob("*.csv", "-name").unwrap();
+        assert!(glob.is_match("b.csv"));
+        assert!(!glob.is_match("b.CSV"));
+        assert!(!glob.is_match("b.csv.txt"));
+
+        let glob = parse_glob("*.csv", "-iname").unwrap();
+        assert!(glob.is_match("b.CSV"));
+
+        // -pathの * は / にもマッチする
+        let glob = parse_glob("*/b/*", "-path").unwrap();
+        assert!(glob.is_match(Path::new("tests/inputs/a/b/c/c.mp3")));
+        assert!(!glob.is_match(Path::new("tests/inputs/d/b.csv")));
+    }
+
+    #[test]
+    fn test_parse_regex() {
+        // パス全体にマッチしなければならない
+        let re = parse_regex(".*/a/.*[.]txt", "-regex").unwrap();
+        assert!(re.is_match("tests/inputs/a/a.txt"));
+        assert!(!re.is_match("tests/inputs/a/a.txt~"));
+
+        let re = parse_regex(".*[.]TXT", "-iregex").unwrap();
+        assert!(re.is_match("tests/inputs/a/a.txt"));
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(Compare::parse("+3"), Some(Compare::Greater(3)));
+        assert!(Compare::Greater(3).matches(4));
+        assert!(!Compare::Greater(3).matches(3));
+        assert!(Compare::Less(3).matches(2));
+        assert!(!Compare::Less(3).matches(3));
+        assert!(Compare::Equal(3).matches(3));
+        assert_eq!(Compare::parse("-"), None);
+        assert_eq!(Compare::parse("1.5"), None);
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        // 空の式は常に真
+        assert!(matches!(parse(&[]), Ok(Expr::True)));
+
+        // -a は -o より優先される
+        assert_eq!(
+            parse_str("-true -o -false -false"),
+            parse_str("-true -o ( -false -a -false )"),
+        );
+        assert!(matches!(
+            parse(&args("-true -o -false -false")),
+            Ok(Expr::Or(_, _))
+        ));
+
+        // ! は -a より優先される
+        assert!(matches!(
+            parse(&args("! -true -a -false")),
+            Ok(Expr::And(_, _))
+        ));
+        assert!(matches!(
+            parse(&args("-not ( -true -o -false )")),
+            Ok(Expr::Not(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_delete() {
+        let check = |line: &str| parse(&args(line)).unwrap().check_delete().is_ok();
+        assert!(check("-name a -delete"));
+        assert!(check("-name a -o -delete"));
+        assert!(check("( -type f -true ) -delete"));
+        assert!(check("! -empty -delete"));
+        assert!(check("-print"));
+        assert!(!check("-delete"));
+        assert!(!check("-delete -name a"));
+        assert!(!check("( -delete ) -name a"));
+        assert!(!check("-true -delete -name a"));
+        assert!(!check("-print -o -delete"));
+        assert!(!check("! -delete"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_str("-foo"), "unknown predicate \"-foo\"");
+        assert_eq!(parse_str("-name"), "missing argument to \"-name\"");
+        assert_eq!(parse_str("-name [a"), "invalid -name \"[a\"");
+        assert_eq!(parse_str("-regex *.csv"), "invalid -regex \"*.csv\"");
+        assert_eq!(parse_str("-type x"), "invalid -type \"x\"");
+        assert_eq!(parse_str("-type f,"), "invalid -type \"f,\"");
+        assert_eq!(parse_str("-xtype f,x"), "invalid -xtype \"f,x\"");
+        assert_eq!(parse_str("-type f,l,s"), "Type([File, Link, Socket])");
+        assert_eq!(parse_str("-size 1x"), "invalid -size \"1x\"");
+        assert_eq!(parse_str("-mtime x"), "invalid -mtime \"x\"");
+        assert_eq!(parse_str("-perm 9"), "invalid -perm \"9\"");
+        assert_eq!(parse_str("-links x"), "invalid -links \"x\"");
+        assert_eq!(parse_str("-contains (a"), "invalid -contains \"(a\"");
+        assert_eq!(parse_str("-o -true"), "\"-o\" has no expression before it");
+        assert_eq!(parse_str("-true -o"), "expected an expression after \"-o\"");
+        assert_eq!(parse_str("!"), "expected an expression after \"!\"");
+        assert_eq!(parse_str("( -true"), "missing \")\"");
+        assert_eq!(parse_str("-true )"), "unexpected \")\"");
+        assert_eq!(parse_str("( )"), "empty parentheses are not allowed");
+        assert_eq!(
+            parse_str("-name a -delete"),
+            parse_str("-name a -a -delete")
+        );
+        assert_eq!(
+            parse_str("-true tests"),
+            "paths must precede the expression: \"tests\""
+        );
+    }
+}
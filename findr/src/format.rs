@@ -0,0 +1,352 @@
+use crate::{expr::TimeKind, sys, walk::Entry, MyResult};
+use std::{
+    fs::{self, Metadata},
+    io::Write,
+    mem,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// -printfの書式。
+#[derive(Debug)]
+pub struct Format(Vec<Token>);
+
+#[derive(Debug, Eq, PartialEq)]
+enum Token {
+    Literal(String),
+    Field(char),
+    // %a, %c, %t はctime(3)の形式、%Ak などはその一部を出力する。
+    Time(TimeKind, Option<char>),
+}
+
+impl Format {
+    pub fn parse(val: &str) -> MyResult<Format> {
+        let mut tokens = vec![];
+        let mut literal = String::new();
+        let mut chars = val.chars();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '\\' => {
+                    literal.push(match chars.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('a') => '\x07',
+                        Some('\\') => '\\',
+                        Some(c) => {
+                            return Err(From::from(format!("invalid -printf escape \"\\{}\"", c)))
+                        }
+                        None => '\\',
+                    });
+                    continue;
+                }
+                '%' => match chars.next() {
+                    Some('%') => {
+                        literal.push('%');
+                        continue;
+                    }
+                    Some(
+                        c @ ('p' | 'f' | 'h' | 's' | 'k' | 'm' | 'M' | 'u' | 'g' | 'U' | 'G' | 'i'
+                        | 'n' | 'y' | 'd' | 'l'),
+                    ) => Token::Field(c),
+                    Some(c @ ('a' | 'c' | 't')) => Token::Time(time_kind(c), None),
+                    Some(c @ ('A' | 'C' | 'T')) => match chars.next() {
+                        Some(
+                            k @ ('Y' | 'm' | 'd' | 'H' | 'M' | 'S' | 'T' | 'F' | '+' | '@' | 'a'
+                            | 'b' | 'j'),
+                        ) => Token::Time(time_kind(c), Some(k)),
+                        Some(k) => {
+                            return Err(From::from(format!(
+                                "invalid -printf directive \"%{}{}\"",
+                                c, k
+                            )))
+                        }
+                        None => {
+                            return Err(From::from(format!("invalid -printf directive \"%{}\"", c)))
+                        }
+                    },
+                    Some(c) => {
+                        return Err(From::from(format!("invalid -printf directive \"%{}\"", c)))
+                    }
+                    None => return Err(From::from("invalid -printf directive \"%\"")),
+                },
+                _ => {
+                    literal.push(c);
+                    continue;
+                }
+            };
+            if !literal.is_empty() {
+                tokens.push(Token::Literal(mem::take(&mut literal)));
+            }
+            tokens.push(token);
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(Format(tokens))
+    }
+
+    // メタデータが読めない場合、それに依存する項目は空になる。
+    pub fn write(&self, entry: &Entry, out: &mut Vec<u8>) {
+        let metadata = entry.metadata().ok();
+        for token in &self.0 {
+            match token {
+                Token::Literal(text) => out.extend_from_slice(text.as_bytes()),
+                Token::Field(c) => write_field(*c, entry, metadata.as_ref(), out),
+                Token::Time(kind, field) => {
+                    if let Some(time) = metadata.as_ref().and_then(|m| kind.get(m).ok()) {
+                        write_time(time, *field, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn time_kind(c: char) -> TimeKind {
+    match c {
+        'a' | 'A' => TimeKind::Accessed,
+        'c' | 'C' => TimeKind::Changed,
+        _ => TimeKind::Modified,
+    }
+}
+
+fn write_field(c: char, entry: &Entry, metadata: Option<&Metadata>, out: &mut Vec<u8>) {
+    let path = entry.path();
+    let _ = match c {
+        'p' => write!(out, "{}", path.display()),
+        'f' => write!(out, "{}", entry.file_name().to_string_lossy()),
+        'h' => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => write!(out, "{}", parent.display()),
+            _ => write!(out, "."),
+        },
+        'y' => write!(out, "{}", sys::type_char(&entry.file_type())),
+        'd' => write!(out, "{}", entry.depth()),
+        'l' => match fs::read_link(path) {
+            Ok(target) if entry.path_is_symlink() => write!(out, "{}", target.display()),
+            _ => Ok(()),
+        },
+        _ => match metadata {
+            None => Ok(()),
+            Some(metadata) => match c {
+                's' => write!(out, "{}", metadata.len()),
+                'k' => write!(out, "{}", sys::kib(metadata)),
+                'm' => write!(out, "{:o}", sys::mode(metadata) & 0o7777),
+                'M' => write!(out, "{}", mode_string(sys::mode(metadata))),
+                'u' => write!(out, "{}", user(metadata)),
+                'g' => write!(out, "{}", group(metadata)),
+                'U' => write!(out, "{}", sys::uid(metadata)),
+                'G' => write!(out, "{}", sys::gid(metadata)),
+                'i' => write!(out, "{}", sys::ino(metadata)),
+                'n' => write!(out, "{}", sys::nlink(metadata)),
+                _ => unreachable!(),
+            },
+        },
+    };
+}
+
+fn write_time(time: SystemTime, field: Option<char>, out: &mut Vec<u8>) {
+    let tm = sys::local_time(time);
+    let _ = match field {
+        None => write!(
+            out,
+            "{} {} {:2} {:02}:{:02}:{:02} {}",
+            DAYS[tm.wday as usize],
+            MONTHS[tm.month as usize - 1],
+            tm.day,
+            tm.hour,
+            tm.min,
+            tm.sec,
+            tm.year
+        ),
+        Some('Y') => write!(out, "{}", tm.year),
+        Some('m') => write!(out, "{:02}", tm.month),
+        Some('d') => write!(out, "{:02}", tm.day),
+        Some('H') => write!(out, "{:02}", tm.hour),
+        Some('M') => write!(out, "{:02}", tm.min),
+        Some('S') => write!(out, "{:02}", tm.sec),
+        Some('T') => write!(out, "{:02}:{:02}:{:02}", tm.hour, tm.min, tm.sec),
+        Some('F') => write!(out, "{}-{:02}-{:02}", tm.year, tm.month, tm.day),
+        Some('+') => write!(
+            out,
+            "{}-{:02}-{:02}+{:02}:{:02}:{:02}",
+            tm.year, tm.month, tm.day, tm.hour, tm.min, tm.sec
+        ),
+        Some('@') => match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => write!(out, "{}", d.as_secs()),
+            Err(e) => write!(out, "-{}", e.duration().as_secs()),
+        },
+        Some('a') => write!(out, "{}", DAYS[tm.wday as usize]),
+        Some('b') => write!(out, "{}", MONTHS[tm.month as usize - 1]),
+        Some('j') => write!(out, "{:03}", tm.yday + 1),
+        Some(_) => unreachable!(),
+    };
+}
+
+// -lsの1行。`find -ls` と同じく `ls -dils` 相当の項目を並べる。
+pub fn write_ls(entry: &Entry, now: SystemTime, out: &mut Vec<u8>) {
+    let metadata = match entry.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let _ = write!(
+        out,
+        "{:6} {:4} {} {:3} {:<8} {:<8} {:8} {} {}",
+        sys::ino(&metadata),
+        sys::kib(&metadata),
+        mode_string(sys::mode(&metadata)),
+        sys::nlink(&metadata),
+        user(&metadata),
+        group(&metadata),
+        metadata.len(),
+        ls_time(metadata.modified().unwrap_or(UNIX_EPOCH), now),
+        entry.path().display()
+    );
+    if entry.path_is_symlink() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            let _ = write!(out, " -> {}", target.display());
+        }
+    }
+    out.push(b'\n');
+}
+
+// lsと同じく、半年以上前か未来の日時は時刻の代わりに年を表示する。
+fn ls_time(time: SystemTime, now: SystemTime) -> String {
+    const HALF_YEAR: Duration = Duration::from_secs(365 * 24 * 60 * 60 / 2);
+    let tm = sys::local_time(time);
+    let recent = now
+        .duration_since(time)
+        .map(|age| age < HALF_YEAR)
+        .unwrap_or(false);
+    if recent {
+        format!(
+            "{} {:2} {:02}:{:02}",
+            MONTHS[tm.month as usize - 1],
+            tm.day,
+            tm.hour,
+            tm.min
+        )
+    } else {
+        format!(
+            "{} {:2}  {}",
+            MONTHS[tm.month as usize - 1],
+            tm.day,
+            tm.year
+        )
+    }
+}
+
+fn user(metadata: &Metadata) -> String {
+    let uid = sys::uid(metadata);
+    sys::user_name(uid).unwrap_or_else(|| uid.to_string())
+}
+
+fn group(metadata: &Metadata) -> String {
+    let gid = sys::gid(metadata);
+    sys::group_name(gid).unwrap_or_else(|| gid.to_string())
+}
+
+// `-rwxr-xr-x` の形式
+fn mode_string(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '-',
+    };
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    for (shift, special, set) in [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')] {
+        let bits = mode >> shift;
+        s.push(if bits & 4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 2 != 0 { 'w' } else { '-' });
+        s.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mode_string, Format, Token};
+    use crate::expr::TimeKind;
+
+    #[test]
+    fn test_parse() {
+        let format = Format::parse("%f %s\\n").unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                Token::Field('f'),
+                Token::Literal(" ".to_string()),
+                Token::Field('s'),
+                Token::Literal("\n".to_string()),
+            ]
+        );
+
+        let format = Format::parse("100%% %TY-%Tm-%Td %t\\0").unwrap();
+        assert_eq!(
+            format.0,
+            vec![
+                Token::Literal("100% ".to_string()),
+                Token::Time(TimeKind::Modified, Some('Y')),
+                Token::Literal("-".to_string()),
+                Token::Time(TimeKind::Modified, Some('m')),
+                Token::Literal("-".to_string()),
+                Token::Time(TimeKind::Modified, Some('d')),
+                Token::Literal(" ".to_string()),
+                Token::Time(TimeKind::Modified, None),
+                Token::Literal("\0".to_string()),
+            ]
+        );
+
+        let res = Format::parse("%z");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid -printf directive \"%z\""
+        );
+
+        let res = Format::parse("%Tq");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid -printf directive \"%Tq\""
+        );
+
+        let res = Format::parse("%");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid -printf directive \"%\""
+        );
+
+        let res = Format::parse("\\q");
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "invalid -printf escape \"\\q\""
+        );
+    }
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(0o100644), "-rw-r--r--");
+        assert_eq!(mode_string(0o040755), "drwxr-xr-x");
+        assert_eq!(mode_string(0o120777), "lrwxrwxrwx");
+        assert_eq!(mode_string(0o104755), "-rwsr-xr-x");
+        assert_eq!(mode_string(0o041777), "drwxrwxrwt");
+        assert_eq!(mode_string(0o102644), "-rw-r-Sr--");
+    }
+}
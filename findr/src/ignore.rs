@@ -0,0 +1,272 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// 走査中のディレクトリまでに読んだ無視ルール。
+// 深いディレクトリのものほど、同じディレクトリでは後に読んだものほど優先される。
+#[derive(Debug)]
+pub struct Ignore {
+    parent: Option<Arc<Ignore>>,
+    files: Vec<Gitignore>,
+    // gitのリポジトリの中か。.gitignoreはリポジトリの中でだけ使う。
+    git: bool,
+}
+
+impl Ignore {
+    // 起点のディレクトリのルール。リポジトリの中なら、グローバルな除外設定、
+    // .git/info/exclude、リポジトリのルートから起点までの親ディレクトリのルールも読む。
+    pub fn root(root: &Path) -> Arc<Ignore> {
+        let abs = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let repo = abs.ancestors().find(|dir| dir.join(".git").exists());
+        let mut files = vec![];
+        if let Some(repo) = repo {
+            let prefix = abs.strip_prefix(repo).unwrap_or(Path::new(""));
+            let excludes = global_excludes()
+                .into_iter()
+                .chain([repo.join(".git/info/exclude")]);
+            files.extend(excludes.filter_map(|file| Gitignore::from_file(&file, root, prefix)));
+
+            let parents: Vec<_> = abs
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(repo))
+                .collect();
+            for parent in parents.into_iter().rev() {
+                let prefix = abs.strip_prefix(parent).unwrap_or(Path::new(""));
+                files.extend(load(parent, root, prefix, true));
+            }
+        }
+        files.extend(load(root, root, Path::new(""), repo.is_some()));
+        Arc::new(Ignore {
+            parent: None,
+            files,
+            git: repo.is_some(),
+        })
+    }
+
+    // 子のディレクトリのルール。無視ファイルがなければ親のものを共有する。
+    pub fn child(self: &Arc<Ignore>, dir: &Path) -> Arc<Ignore> {
+        let git = self.git || dir.join(".git").exists();
+        let files = load(dir, dir, Path::new(""), git);
+        if files.is_empty() && git == self.git {
+            return Arc::clone(self);
+        }
+        Arc::new(Ignore {
+            parent: Some(Arc::clone(self)),
+            files,
+            git,
+        })
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut level = Some(self);
+        while let Some(ignore) = level {
+            for file in ignore.files.iter().rev() {
+                if let Some(ignored) = file.matched(path, is_dir) {
+                    return ignored;
+                }
+            }
+            level = ignore.parent.as_deref();
+        }
+        false
+    }
+}
+
+fn load(source: &Path, dir: &Path, prefix: &Path, git: bool) -> Vec<Gitignore> {
+    [(".gitignore", git), (".ignore", true)]
+        .into_iter()
+        .filter(|(_, enabled)| *enabled)
+        .filter_map(|(name, _)| Gitignore::from_file(&source.join(name), dir, prefix))
+        .collect()
+}
+
+// core.excludesFile、なければ $XDG_CONFIG_HOME/git/ignore
+// gitと同じく、~/.gitconfig の設定を $XDG_CONFIG_HOME/git/config より優先する。
+fn global_excludes() -> Option<PathBuf> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")));
+    let configured = [
+        home.as_ref().map(|home| home.join(".gitconfig")),
+        xdg.as_ref().map(|dir| dir.join("git").join("config")),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|file| fs::read_to_string(file).ok())
+    .find_map(|config| excludes_file(&config));
+    match (configured, &home) {
+        (Some(file), Some(home)) if file.starts_with("~/") => Some(home.join(&file[2..])),
+        (Some(file), _) => Some(PathBuf::from(file)),
+        (None, _) => xdg.map(|dir| dir.join("git").join("ignore")),
+    }
+}
+
+fn excludes_file(config: &str) -> Option<String> {
+    let mut core = false;
+    let mut file = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            core = line.eq_ignore_ascii_case("[core]");
+        } else if let Some((key, val)) = line.split_once('=').filter(|_| core) {
+            if key.trim().eq_ignore_ascii_case("excludesfile") {
+                file = Some(val.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    file
+}
+
+// .gitignore形式のファイル1つ分。
+// パターンは dir からの相対パスの前に prefix を付けたものと照合する。
+#[derive(Debug)]
+struct Gitignore {
+    dir: PathBuf,
+    prefix: PathBuf,
+    globs: GlobSet,
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Rule {
+    negate: bool,
+    dir_only: bool,
+}
+
+impl Gitignore {
+    fn from_file(file: &Path, dir: &Path, prefix: &Path) -> Option<Gitignore> {
+        let text = fs::read_to_string(file).ok()?;
+        let gitignore = Gitignore::parse(&text, dir, prefix);
+        (!gitignore.rules.is_empty()).then_some(gitignore)
+    }
+
+    fn parse(text: &str, dir: &Path, prefix: &Path) -> Gitignore {
+        let mut builder = GlobSetBuilder::new();
+        let mut rules = vec![];
+        for (glob, rule) in text.lines().filter_map(parse_line) {
+            let glob = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .backslash_escape(true)
+                .build();
+            // gitと同じく、解釈できない行は無視する。
+            if let Ok(glob) = glob {
+                builder.add(glob);
+                rules.push(rule);
+            }
+        }
+        Gitignore {
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_path_buf(),
+            globs: builder.build().unwrap_or_else(|_| GlobSet::empty()),
+            rules,
+        }
+    }
+
+    // 無視するならSome(true)、! で除外されていればSome(false)
+    fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let path = self.prefix.join(path.strip_prefix(&self.dir).ok()?);
+        self.globs
+            .matches(path)
+            .into_iter()
+            .filter(|&i| is_dir || !self.rules[i].dir_only)
+            .max()
+            .map(|i| !self.rules[i].negate)
+    }
+}
+
+// `/` を含むパターンはそのディレクトリからの相対パス、含まなければ名前と照合する。
+fn parse_line(line: &str) -> Option<(String, Rule)> {
+    let mut line = line.strip_suffix('\r').unwrap_or(line);
+    if line.starts_with('#') {
+        return None;
+    }
+    while line.ends_with(' ') && !line.ends_with("\\ ") {
+        line = &line[..line.len() - 1];
+    }
+    let negate = line.starts_with('!');
+    if negate {
+        line = &line[1..];
+    }
+    if line.starts_with("\\!") || line.starts_with("\\#") {
+        line = &line[1..];
+    }
+    let dir_only = line.ends_with('/');
+    if dir_only {
+        line = &line[..line.len() - 1];
+    }
+    if line.is_empty() {
+        return None;
+    }
+    let glob = match line.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if line.contains('/') => line.to_string(),
+        None => format!("**/{}", line),
+    };
+    Some((glob, Rule { negate, dir_only }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{excludes_file, parse_line, Gitignore, Rule};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_line() {
+        let rule = Rule {
+            negate: false,
+            dir_only: false,
+        };
+        assert_eq!(parse_line("target"), Some(("**/target".to_string(), rule)));
+        assert_eq!(parse_line("/target"), Some(("target".to_string(), rule)));
+        assert_eq!(parse_line("a/*.rs  "), Some(("a/*.rs".to_string(), rule)));
+        assert_eq!(parse_line("\\#x"), Some(("**/#x".to_string(), rule)));
+        assert_eq!(
+            parse_line("!keep/"),
+            Some((
+                "**/keep".to_string(),
+                Rule {
+                    negate: true,
+                    dir_only: true
+                }
+            ))
+        );
+        assert_eq!(parse_line("# comment"), None);
+        assert_eq!(parse_line(""), None);
+        assert_eq!(parse_line("/"), None);
+    }
+
+    #[test]
+    fn test_matched() {
+        let text = "target/\n*.log\n!keep.log\n/build\ndoc/*.html\n";
+        let gitignore = Gitignore::parse(text, Path::new("root"), Path::new(""));
+        let matched = |path: &str, is_dir| gitignore.matched(Path::new(path), is_dir);
+        assert_eq!(matched("root/target", true), Some(true));
+        assert_eq!(matched("root/a/target", true), Some(true));
+        assert_eq!(matched("root/target", false), None);
+        assert_eq!(matched("root/a/b.log", false), Some(true));
+        assert_eq!(matched("root/a/keep.log", false), Some(false));
+        assert_eq!(matched("root/build", true), Some(true));
+        assert_eq!(matched("root/a/build", true), None);
+        assert_eq!(matched("root/doc/a.html", false), Some(true));
+        assert_eq!(matched("root/doc/api/a.html", false), None);
+        assert_eq!(matched("other/a.log", false), None);
+
+        // 起点より上のディレクトリのファイルは、起点までのパスを補って照合する
+        let gitignore = Gitignore::parse("/sub/build\n", Path::new("."), Path::new("sub"));
+        assert_eq!(gitignore.matched(Path::new("./build"), true), Some(true));
+    }
+
+    #[test]
+    fn test_excludes_file() {
+        let config = "[user]\n\tname = x\n[core]\n\texcludesFile = ~/.gitignore_global\n";
+        assert_eq!(
+            excludes_file(config),
+            Some("~/.gitignore_global".to_string())
+        );
+        assert_eq!(excludes_file("[user]\n\texcludesfile = x\n"), None);
+    }
+}
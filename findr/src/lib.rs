@@ -1,16 +1,122 @@
+mod content;
+mod duplicates;
+mod exec;
+mod expr;
+mod format;
+mod ignore;
+mod summary;
+mod sys;
+mod walk;
+
 use crate::EntryType::*;
+use crate::expr::{Context, Expr};
+use crate::ignore::Ignore;
+use crate::walk::{Entry, Output};
 use clap::{App, Arg};
 use regex::Regex;
-use std::error::Error;
-use walkdir::{DirEntry, WalkDir};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fs::{self, FileType, Metadata},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::SystemTime,
+};
+use walkdir::WalkDir;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
-    names: Vec<Regex>,
-    entry_types: Vec<EntryType>,
+    expr: Expr,
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    dry_run: bool,
+    follow: Follow,
+    threads: usize,
+    sorted: bool,
+    sort: Option<Sort>,
+    summary: Option<usize>,
+    duplicates: bool,
+    json: bool,
+    gitignore: bool,
+    no_hidden: bool,
+}
+
+// シンボリックリンクを辿る範囲。-P (既定), -H, -L に対応する。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Follow {
+    Never,
+    Roots,
+    Always,
+}
+
+// --sortと--reverseで指定する、同じディレクトリのエントリの順。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Sort {
+    key: SortKey,
+    reverse: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+// 名前の前に比べる大きさと更新日時。キーにないほうはNoneのままにする。
+type SortValue = (Option<u64>, Option<SystemTime>);
+
+impl Sort {
+    // メタデータは必要なときだけ読む。
+    fn value(self, metadata: impl FnOnce() -> Option<Metadata>) -> SortValue {
+        match self.key {
+            SortKey::Name => (None, None),
+            SortKey::Size => (metadata().map(|m| m.len()), None),
+            SortKey::Modified => (None, metadata().and_then(|m| m.modified().ok())),
+        }
+    }
+
+    // 大きさや更新日時が同じものは名前の順にする。
+    fn compare(self, a: &OsStr, a_value: SortValue, b: &OsStr, b_value: SortValue) -> Ordering {
+        let ord = a_value.cmp(&b_value).then_with(|| a.cmp(b));
+        match self.reverse {
+            true => ord.reverse(),
+            false => ord,
+        }
+    }
+
+    // walkdirのsort_byに渡す比較関数。比べるたびにstatしないよう、
+    // 並べているディレクトリの間だけ各エントリの値を覚えておく。
+    fn comparator(
+        self,
+    ) -> impl FnMut(&walkdir::DirEntry, &walkdir::DirEntry) -> Ordering + Send + Sync + 'static
+    {
+        let mut dir = PathBuf::new();
+        let mut values: HashMap<OsString, SortValue> = HashMap::new();
+        move |a, b| {
+            if let Some(parent) = a.path().parent().filter(|parent| *parent != dir) {
+                dir = parent.to_path_buf();
+                values.clear();
+            }
+            let mut value = |entry: &walkdir::DirEntry| match values.get(entry.file_name()) {
+                Some(value) => *value,
+                None => {
+                    let value = self.value(|| entry.metadata().ok());
+                    values.insert(entry.file_name().to_os_string(), value);
+                    value
+                }
+            };
+            let (a_value, b_value) = (value(a), value(b));
+            self.compare(a.file_name(), a_value, b.file_name(), b_value)
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -18,13 +124,106 @@ enum EntryType {
     Dir,
     File,
     Link,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+}
+
+impl EntryType {
+    fn parse(val: &str) -> Option<EntryType> {
+        match val {
+            "d" => Some(Dir),
+            "f" => Some(File),
+            "l" => Some(Link),
+            "b" => Some(BlockDevice),
+            "c" => Some(CharDevice),
+            "p" => Some(Fifo),
+            "s" => Some(Socket),
+            _ => None,
+        }
+    }
+
+    // `f,l` のようなカンマ区切りの一覧
+    fn parse_list(val: &str) -> Option<Vec<EntryType>> {
+        val.split(',').map(EntryType::parse).collect()
+    }
+
+    fn matches(&self, file_type: &FileType) -> bool {
+        let c = sys::type_char(file_type);
+        match self {
+            Link => c == 'l',
+            Dir => c == 'd',
+            File => c == 'f',
+            BlockDevice => c == 'b',
+            CharDevice => c == 'c',
+            Fifo => c == 'p',
+            Socket => c == 's',
+        }
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
+    // 最初の式の引数より前をclapで解析し、残りを式として解析する。
+    let args: Vec<String> = env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let (args, expr_args) = args.split_at(
+        args.iter()
+            .skip(1)
+            .position(|arg| expr::is_expression_start(arg))
+            .map_or(args.len(), |i| i + 1),
+    );
+
     let matches = App::new("findr")
         .version("0.1.0")
         .author("naoyuki miyata(n.miyata080825@gmail.com")
         .about("rust find")
+        .usage("findr [-H|-L|-P] [OPTIONS] [paths]... [EXPRESSION]")
+        .after_help(
+            "EXPRESSION:
+    Operators (highest precedence first):
+        ( EXPR )    ! EXPR, -not EXPR    EXPR [-a|-and] EXPR    EXPR -o|-or EXPR
+    Actions:
+        -print    -print0    -printf FORMAT    -ls
+        -exec CMD ;    -exec CMD {} +    -execdir CMD ;    -execdir CMD {} +
+        -ok CMD ;    -okdir CMD ;    -prune    -delete
+    Options (always true, apply to the whole search):
+        -xdev, -mount    don't descend into other file systems
+    Tests:
+        -name GLOB    -iname GLOB    -path GLOB    -ipath GLOB
+        -regex REGEX    -iregex REGEX    -type TYPES    -xtype TYPES
+        -true    -false
+        -size [+-]N[cwbkMG]    -atime|-ctime|-mtime [+-]N (days)
+        -amin|-cmin|-mmin [+-]N (minutes)    -newer FILE    -empty
+        -perm [-/]MODE    -user NAME    -group NAME    -uid [+-]N    -gid [+-]N
+        -nouser    -nogroup    -inum [+-]N    -links [+-]N    -samefile FILE
+        -contains REGEX    -mime TYPE
+
+    -printf FORMAT understands \\n, \\t, \\0, %% and the directives
+        %p path    %f name    %h directory    %s size    %k KiB used
+        %m octal mode    %M symbolic mode    %u user    %g group    %U uid
+        %G gid    %i inode    %n links    %y type    %d depth    %l link target
+        %a %c %t access/change/modify time, %Ak %Ck %Tk one field of it
+        (k is one of Y m d H M S T F + @ a b j)
+
+    TYPES is a comma-separated list of f (file), d (directory), l (symbolic
+    link), b (block device), c (character device), p (FIFO) and s (socket).
+    -xtype checks the type of a symbolic link's target instead (a broken link
+    is l), or of the link itself when links are followed.
+
+    -contains matches REGEX against each line of a regular file. -mime
+    guesses the type of a regular file from its first bytes (text/plain,
+    image/png, application/gzip, application/x-executable, ...); TYPE is
+    either a full type, or image/* or image for any image.
+
+    -perm MODE is octal (644) or symbolic (u=rw,go=r). MODE matches exactly,
+    -MODE requires all of its bits and /MODE any of them.
+
+    -name and -path match shell globs; -regex matches the whole path. The
+    expression must follow all paths and options. --name (an unanchored regex
+    on the file name) and --type are ANDed with it.",
+        )
         .arg(
             Arg::with_name("paths")
                 .help("search path [default: .]")
@@ -47,11 +246,125 @@ pub fn get_args() -> MyResult<Config> {
                 .long("type")
                 .value_name("TYPE")
                 .takes_value(true)
-                .possible_values(&["f", "d", "l"])
+                .possible_values(&["f", "d", "l", "b", "c", "p", "s"])
+                .use_delimiter(true)
                 .help("entry type")
                 .multiple(true),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("contains")
+                .long("contains")
+                .value_name("REGEX")
+                .takes_value(true)
+                .help("regular files with a line matching REGEX (same as -contains)"),
+        )
+        .arg(
+            Arg::with_name("mime")
+                .long("mime")
+                .value_name("TYPE")
+                .takes_value(true)
+                .help("regular files whose contents look like TYPE (same as -mime)"),
+        )
+        .arg(
+            Arg::with_name("min_depth")
+                .long("min-depth")
+                .value_name("N")
+                .takes_value(true)
+                .help("ignore entries shallower than N (0 is the search path)"),
+        )
+        .arg(
+            Arg::with_name("max_depth")
+                .long("max-depth")
+                .value_name("N")
+                .takes_value(true)
+                .help("descend at most N levels below the search path"),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("L")
+                .overrides_with_all(&["follow_roots", "no_follow"])
+                .help("follow symbolic links"),
+        )
+        .arg(
+            Arg::with_name("follow_roots")
+                .short("H")
+                .overrides_with_all(&["follow", "no_follow"])
+                .help("follow symbolic links given as paths only"),
+        )
+        .arg(
+            Arg::with_name("no_follow")
+                .short("P")
+                .overrides_with_all(&["follow", "follow_roots"])
+                .help("never follow symbolic links (default)"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("j")
+                .long("threads")
+                .value_name("N")
+                .takes_value(true)
+                .help("walk directories with N threads (0: one per CPU)"),
+        )
+        .arg(
+            Arg::with_name("sorted")
+                .long("sorted")
+                .help("print results sorted by path once the walk is done"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("KEY")
+                .takes_value(true)
+                .possible_values(&["name", "size", "mtime"])
+                .conflicts_with("sorted")
+                .help("visit the entries of each directory in order of KEY"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .long("reverse")
+                .requires("sort")
+                .help("reverse the order of --sort"),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .help("print counts per type, total size, the largest files and the deepest path of the matches"),
+        )
+        .arg(
+            Arg::with_name("largest")
+                .long("largest")
+                .value_name("N")
+                .takes_value(true)
+                .requires("summary")
+                .help("number of largest files --summary shows [default: 5]"),
+        )
+        .arg(
+            Arg::with_name("duplicates")
+                .long("duplicates")
+                .help("print groups of matching non-empty files with the same contents"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .requires("duplicates")
+                .help("print --duplicates groups as JSON"),
+        )
+        .arg(
+            Arg::with_name("gitignore")
+                .long("respect-gitignore")
+                .help("skip entries ignored by .gitignore, .ignore and git's excludes"),
+        )
+        .arg(
+            Arg::with_name("no_hidden")
+                .long("no-hidden")
+                .help("skip hidden entries (names starting with '.')"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .help("print what -delete would remove without removing it"),
+        )
+        .get_matches_from(args);
 
     let names = matches
         .values_of_lossy("names")
@@ -63,62 +376,284 @@ pub fn get_args() -> MyResult<Config> {
         .transpose()?
         .unwrap_or_default();
 
-    let entry_types = matches
+    let entry_types: Vec<EntryType> = matches
         .values_of_lossy("entry_types")
         .map(|vals| {
             vals.iter()
-                .map(|val| match val.as_str() {
-                    "d" => Dir,
-                    "f" => File,
-                    "l" => Link,
-                    _ => unreachable!("Invalid type"),
-                })
+                .map(|val| EntryType::parse(val).expect("Invalid type"))
                 .collect()
         })
         .unwrap_or_default();
 
+    // --containsと--mimeは式と同じものを作り、--nameや--typeで絞り込んだ後に評価する。
+    let mut content_args = vec![];
+    for name in ["contains", "mime"] {
+        if let Some(val) = matches.value_of(name) {
+            content_args.extend([format!("-{}", name), val.to_string()]);
+        }
+    }
+    let content = expr::parse(&content_args)?;
+
+    // --nameはOR、--typeもOR、両者と式はANDで結合する。
+    let names = names
+        .into_iter()
+        .map(Expr::NameRegex)
+        .reduce(Expr::or)
+        .unwrap_or(Expr::True);
+    let entry_types = match entry_types {
+        types if types.is_empty() => Expr::True,
+        types => Expr::Type(types),
+    };
+    let mut expr = entry_types
+        .and(names)
+        .and(content)
+        .and(expr::parse(expr_args)?);
+    expr.check_delete()?;
+    // --summaryや--duplicatesだけなら一覧は表示しない。
+    let summary = match matches.value_of("largest") {
+        _ if !matches.is_present("summary") => None,
+        Some(val) => Some(parse_number(val, "--largest")?),
+        None => Some(5),
+    };
+    let duplicates = matches.is_present("duplicates");
+    if !expr.has_action() && summary.is_none() && !duplicates {
+        expr = expr.and(Expr::Print);
+    }
+
+    let min_depth = matches
+        .value_of("min_depth")
+        .map(|val| parse_number(val, "--min-depth"))
+        .transpose()?;
+    let max_depth = matches
+        .value_of("max_depth")
+        .map(|val| parse_number(val, "--max-depth"))
+        .transpose()?;
+
+    let threads = match matches.value_of("threads") {
+        None => 1,
+        Some(val) => match parse_number(val, "--threads")? {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        },
+    };
+
+    let sort = matches.value_of("sort").map(|val| Sort {
+        key: match val {
+            "size" => SortKey::Size,
+            "mtime" => SortKey::Modified,
+            _ => SortKey::Name,
+        },
+        reverse: matches.is_present("reverse"),
+    });
+    // 並列の走査ではディレクトリ間の順が決まらないので、--sortは1スレッドでのみ使える。
+    if sort.is_some() && threads > 1 {
+        return Err(From::from(
+            "--sort cannot be used with --threads (try --sorted)",
+        ));
+    }
+
+    // 削除は走査の後に深い順に行うので、/ の中身を消し始める前にここで拒む。
+    let paths = matches.values_of_lossy("paths").unwrap();
+    if expr.has_delete() {
+        if let Some(path) = paths.iter().find(|path| is_root(Path::new(path))) {
+            return Err(From::from(format!(
+                "{}: refusing to delete the root directory",
+                path
+            )));
+        }
+    }
+
     Ok(Config {
-        paths: matches.values_of_lossy("paths").unwrap(),
-        names: names,
-        entry_types: entry_types,
+        paths,
+        expr,
+        min_depth,
+        max_depth,
+        dry_run: matches.is_present("dry_run"),
+        follow: if matches.is_present("follow") {
+            Follow::Always
+        } else if matches.is_present("follow_roots") {
+            Follow::Roots
+        } else {
+            Follow::Never
+        },
+        threads,
+        sorted: matches.is_present("sorted"),
+        sort,
+        summary,
+        duplicates,
+        json: matches.is_present("json"),
+        gitignore: matches.is_present("gitignore"),
+        no_hidden: matches.is_present("no_hidden"),
     })
 }
 
+fn parse_number(val: &str, name: &str) -> MyResult<usize> {
+    val.parse()
+        .map_err(|_| From::from(format!("invalid {} \"{}\"", name, val)))
+}
+
 pub fn run(config: Config) -> MyResult<()> {
-    let type_filter = |entry: &DirEntry| {
-        config.entry_types.is_empty()
-            || config
-                .entry_types
-                .iter()
-                .any(|entry_type| match entry_type {
-                    Link => entry.file_type().is_symlink(),
-                    Dir => entry.file_type().is_dir(),
-                    File => entry.file_type().is_file(),
-                })
+    let output = Output::new(&config);
+    let to_delete = match config.threads {
+        1 => walk(&config, &output)?,
+        threads => walk::parallel(&config, threads, &output)?,
     };
-    let name_filter = |entry: &DirEntry| {
-        config.names.is_empty()
-            || config
-                .names
-                .iter()
-                .any(|re| re.is_match(&entry.file_name().to_string_lossy()))
-    };
-    for path in config.paths {
-        let entries = WalkDir::new(path)
-            .into_iter()
-            .filter_map(|e| match e {
-                Err(e) => {
-                    eprintln!("{}", e);
-                    None
+    output.finish()?;
+    if config.expr.finish() {
+        return Err(From::from("one or more commands failed"));
+    }
+    if !delete(to_delete, config.dry_run) {
+        return Err(From::from("one or more entries could not be deleted"));
+    }
+    Ok(())
+}
+
+// 1スレッドでの走査。削除するエントリを返す。
+fn walk(config: &Config, output: &Output) -> MyResult<Vec<PathBuf>> {
+    let mut ctx = Context::default();
+    let mut to_delete = vec![];
+    // 深さごとの、そのディレクトリまでの無視ルール
+    let mut ignores: Vec<Arc<Ignore>> = vec![];
+    for path in &config.paths {
+        // --min-depthより浅いディレクトリの無視ルールも読むため、深さは自分で判定する。
+        let mut walker = WalkDir::new(path)
+            .follow_links(config.follow == Follow::Always)
+            .follow_root_links(config.follow != Follow::Never)
+            .same_file_system(config.expr.xdev());
+        if let Some(depth) = config.max_depth {
+            walker = walker.max_depth(depth);
+        }
+        if let Some(sort) = config.sort {
+            walker = walker.sort_by(sort.comparator());
+        }
+        let mut entries = walker.into_iter();
+        while let Some(entry) = entries.next() {
+            let entry = match entry {
+                // walkdirは辿った起点のリンクもリンクとして返すので、リンク先のエントリにする。
+                Ok(entry) if config.follow == Follow::Roots && entry.depth() == 0 => {
+                    match Entry::from_path(entry.path().to_path_buf(), 0, true) {
+                        Ok(followed) => followed,
+                        Err(_) => Entry::from(entry),
+                    }
+                }
+                Ok(entry) => Entry::from(entry),
+                Err(e) => match broken_link(&e, config) {
+                    Some(entry) => entry,
+                    None => {
+                        warn(&e);
+                        continue;
+                    }
+                },
+            };
+            let depth = entry.depth();
+            let is_dir = entry.file_type().is_dir();
+            let parent = depth.checked_sub(1).and_then(|i| ignores.get(i));
+            if walk::is_excluded(config, &entry, parent.map(Arc::as_ref)) {
+                if is_dir {
+                    entries.skip_current_dir();
                 }
-                Ok(entry) => Some(entry),
-            })
-            .filter(type_filter)
-            .filter(name_filter)
-            .map(|entry| entry.path().display().to_string())
-            .collect::<Vec<_>>();
+                continue;
+            }
+            if config.gitignore && is_dir {
+                let ignore = match parent {
+                    Some(parent) => parent.child(entry.path()),
+                    None => Ignore::root(entry.path()),
+                };
+                ignores.truncate(depth);
+                ignores.push(ignore);
+            }
+            if config.min_depth.is_some_and(|min_depth| depth < min_depth) {
+                continue;
+            }
+            if config.expr.eval(&entry, &mut ctx) {
+                output.matched(&entry);
+            }
+            output.write(entry.path(), &mut ctx.out)?;
+            if ctx.prune {
+                entries.skip_current_dir();
+                ctx.prune = false;
+            }
+            to_delete.append(&mut ctx.delete);
+        }
+    }
+    Ok(to_delete)
+}
+
+// 辿ろうとしたリンクの先がなければ、findと同じくリンク自体をエントリとして扱う。
+fn broken_link(e: &walkdir::Error, config: &Config) -> Option<Entry> {
+    let followed = match e.depth() {
+        0 => config.follow != Follow::Never,
+        _ => config.follow == Follow::Always,
+    };
+    if !followed || e.loop_ancestor().is_some() {
+        return None;
+    }
+    let path = e.path()?;
+    if path.exists() {
+        return None;
+    }
+    Entry::from_path(path.to_path_buf(), e.depth(), false)
+        .ok()
+        .filter(|entry| entry.file_type().is_symlink())
+}
 
-        println!("{}", entries.join("\n"));
+fn warn(e: &walkdir::Error) {
+    match (e.path(), e.loop_ancestor()) {
+        (Some(path), Some(ancestor)) => warn_loop(path, ancestor),
+        _ => eprintln!("{}", e),
     }
-    Ok(())
+}
+
+fn warn_loop(path: &Path, ancestor: &Path) {
+    eprintln!(
+        "{}: file system loop detected; it points to its ancestor {}",
+        path.display(),
+        ancestor.display()
+    );
+}
+
+// 深い階層から順に削除する。ディレクトリは空の場合のみ削除できる。
+fn delete(mut paths: Vec<PathBuf>, dry_run: bool) -> bool {
+    paths.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+    let mut ok = true;
+    for path in paths {
+        if is_root(&path) {
+            eprintln!("{}: refusing to delete the root directory", path.display());
+            ok = false;
+            continue;
+        }
+        // findと同じく、起点の . や .. は黙って残す。
+        if is_dot(&path) {
+            continue;
+        }
+        if dry_run {
+            println!("would delete {}", path.display());
+            continue;
+        }
+        let result = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(&path),
+            Ok(_) => fs::remove_file(&path),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", path.display(), e);
+            ok = false;
+        }
+    }
+    ok
+}
+
+// components()は末尾の . を落とすので、文字列のまま最後の要素を見る。
+fn is_dot(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    matches!(
+        path.rsplit(std::path::is_separator).next(),
+        Some("." | "..")
+    )
+}
+
+fn is_root(path: &Path) -> bool {
+    path.canonicalize()
+        .map(|path| path.parent().is_none())
+        .unwrap_or(false)
 }
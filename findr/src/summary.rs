@@ -0,0 +1,92 @@
+// --summaryで表示する、式に一致したエントリの集計。
+use crate::{sys, walk::Entry};
+use std::{
+    cmp::Reverse,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+// 種類ごとの表示名。%yと同じ文字で数える。
+const TYPES: [(char, &str); 8] = [
+    ('d', "directories"),
+    ('f', "files"),
+    ('l', "symbolic links"),
+    ('b', "block devices"),
+    ('c', "character devices"),
+    ('p', "FIFOs"),
+    ('s', "sockets"),
+    ('U', "other"),
+];
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    counts: [u64; TYPES.len()],
+    bytes: u64,
+    // 大きい順 (同じ大きさならパスの順) に最大largest個
+    largest: Vec<(u64, PathBuf)>,
+    limit: usize,
+    deepest: Option<(usize, PathBuf)>,
+}
+
+impl Summary {
+    pub fn new(limit: usize) -> Summary {
+        Summary {
+            limit,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, entry: &Entry) {
+        let c = sys::type_char(&entry.file_type());
+        if let Some(i) = TYPES.iter().position(|(t, _)| *t == c) {
+            self.counts[i] += 1;
+        }
+
+        let deeper = match &self.deepest {
+            None => true,
+            Some((depth, path)) => (Reverse(entry.depth()), entry.path()) < (Reverse(*depth), path),
+        };
+        if deeper {
+            self.deepest = Some((entry.depth(), entry.path().to_path_buf()));
+        }
+
+        if !entry.file_type().is_file() {
+            return;
+        }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        self.bytes += size;
+        let key = (Reverse(size), entry.path());
+        let pos = self
+            .largest
+            .partition_point(|(s, path)| (Reverse(*s), path.as_path()) < key);
+        if pos < self.limit {
+            self.largest.insert(pos, (size, entry.path().to_path_buf()));
+            self.largest.truncate(self.limit);
+        }
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let total: u64 = self.counts.iter().sum();
+        let counts: Vec<String> = TYPES
+            .iter()
+            .zip(self.counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|((_, name), count)| format!("{} {}", count, name))
+            .collect();
+        match counts.is_empty() {
+            true => writeln!(out, "{} entries", total)?,
+            false => writeln!(out, "{} entries: {}", total, counts.join(", "))?,
+        }
+        writeln!(out, "total size of files: {} bytes", self.bytes)?;
+        if !self.largest.is_empty() {
+            writeln!(out, "largest files:")?;
+            for (size, path) in &self.largest {
+                writeln!(out, "{:>12}  {}", size, path.display())?;
+            }
+        }
+        if let Some((depth, path)) = &self.deepest {
+            writeln!(out, "deepest path: {} (depth {})", path.display(), depth)?;
+        }
+        Ok(())
+    }
+}
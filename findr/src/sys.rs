@@ -0,0 +1,299 @@
+// メタデータのうちプラットフォームに依存する部分。
+use std::fs::FileType;
+
+#[cfg(unix)]
+pub use self::unix::*;
+
+#[cfg(not(unix))]
+pub use self::other::*;
+
+// 日時を暦の上の各要素に分けたもの。月は1から、曜日は日曜日を0とする。
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Tm {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub min: u32,
+    pub sec: u32,
+    pub wday: u32,
+    pub yday: u32,
+}
+
+// -typeや%yで使う1文字の種類。
+pub fn type_char(file_type: &FileType) -> char {
+    if file_type.is_symlink() {
+        return 'l';
+    }
+    if file_type.is_dir() {
+        return 'd';
+    }
+    if file_type.is_file() {
+        return 'f';
+    }
+    special_type_char(file_type)
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::Tm;
+    use std::{
+        ffi::{CStr, CString},
+        fs::{FileType, Metadata},
+        mem,
+        os::unix::fs::{FileTypeExt, MetadataExt},
+        ptr,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    pub fn special_type_char(file_type: &FileType) -> char {
+        if file_type.is_block_device() {
+            'b'
+        } else if file_type.is_char_device() {
+            'c'
+        } else if file_type.is_fifo() {
+            'p'
+        } else if file_type.is_socket() {
+            's'
+        } else {
+            'U'
+        }
+    }
+
+    pub fn mode(metadata: &Metadata) -> u32 {
+        metadata.mode()
+    }
+
+    pub fn uid(metadata: &Metadata) -> u32 {
+        metadata.uid()
+    }
+
+    pub fn gid(metadata: &Metadata) -> u32 {
+        metadata.gid()
+    }
+
+    pub fn dev(metadata: &Metadata) -> u64 {
+        metadata.dev()
+    }
+
+    pub fn ino(metadata: &Metadata) -> u64 {
+        metadata.ino()
+    }
+
+    pub fn nlink(metadata: &Metadata) -> u64 {
+        metadata.nlink()
+    }
+
+    // execに渡せる引数と環境変数の合計の大きさ
+    pub fn arg_max() -> Option<usize> {
+        let size = unsafe { libc::sysconf(libc::_SC_ARG_MAX) };
+        (size > 0).then_some(size as usize)
+    }
+
+    // 実際に使っているディスク容量 (KiB)
+    pub fn kib(metadata: &Metadata) -> u64 {
+        (metadata.blocks() * 512).div_ceil(1024)
+    }
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+        let _buf = lookup(libc::_SC_GETPW_R_SIZE_MAX, |buf, len, result| unsafe {
+            libc::getpwuid_r(uid, &mut pwd, buf, len, result)
+        })?;
+        Some(
+            unsafe { CStr::from_ptr(pwd.pw_name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        let mut grp: libc::group = unsafe { mem::zeroed() };
+        let _buf = lookup(libc::_SC_GETGR_R_SIZE_MAX, |buf, len, result| unsafe {
+            libc::getgrgid_r(gid, &mut grp, buf, len, result)
+        })?;
+        Some(
+            unsafe { CStr::from_ptr(grp.gr_name) }
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    pub fn user_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+        lookup(libc::_SC_GETPW_R_SIZE_MAX, |buf, len, result| unsafe {
+            libc::getpwnam_r(name.as_ptr(), &mut pwd, buf, len, result)
+        })?;
+        Some(pwd.pw_uid)
+    }
+
+    pub fn group_id(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        let mut grp: libc::group = unsafe { mem::zeroed() };
+        lookup(libc::_SC_GETGR_R_SIZE_MAX, |buf, len, result| unsafe {
+            libc::getgrnam_r(name.as_ptr(), &mut grp, buf, len, result)
+        })?;
+        Some(grp.gr_gid)
+    }
+
+    // get*_r を呼ぶ。バッファはsysconfが示す大きさから始め、ERANGEの間は2倍にして呼び直す。
+    // 見つかれば、結果の文字列が指しているバッファを返す。
+    fn lookup<T>(
+        size_name: libc::c_int,
+        mut call: impl FnMut(*mut libc::c_char, usize, *mut *mut T) -> libc::c_int,
+    ) -> Option<Vec<libc::c_char>> {
+        let size = unsafe { libc::sysconf(size_name) };
+        let mut buf = vec![0; if size > 0 { size as usize } else { 1024 }];
+        loop {
+            let mut result = ptr::null_mut();
+            match call(buf.as_mut_ptr(), buf.len(), &mut result) {
+                0 if result.is_null() => return None,
+                0 => return Some(buf),
+                libc::ERANGE => buf.resize(buf.len() * 2, 0),
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn local_time(time: SystemTime) -> Tm {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as libc::time_t,
+            Err(e) => -(e.duration().as_secs() as libc::time_t),
+        };
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        unsafe { libc::localtime_r(&secs, &mut tm) };
+        Tm {
+            year: tm.tm_year as i64 + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+            hour: tm.tm_hour as u32,
+            min: tm.tm_min as u32,
+            sec: tm.tm_sec as u32,
+            wday: tm.tm_wday as u32,
+            yday: tm.tm_yday as u32,
+        }
+    }
+}
+
+// Unix以外では所有者やinodeがないので、findが使う既定値で埋める。
+#[cfg(not(unix))]
+mod other {
+    use super::Tm;
+    use std::{
+        fs::{FileType, Metadata},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    pub fn special_type_char(_file_type: &FileType) -> char {
+        'U'
+    }
+
+    pub fn mode(metadata: &Metadata) -> u32 {
+        let perm = if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o644
+        };
+        if metadata.is_dir() {
+            0o040000 | perm | 0o111
+        } else if metadata.file_type().is_symlink() {
+            0o120000 | perm
+        } else {
+            0o100000 | perm
+        }
+    }
+
+    pub fn uid(_metadata: &Metadata) -> u32 {
+        0
+    }
+
+    pub fn gid(_metadata: &Metadata) -> u32 {
+        0
+    }
+
+    pub fn dev(_metadata: &Metadata) -> u64 {
+        0
+    }
+
+    pub fn ino(_metadata: &Metadata) -> u64 {
+        0
+    }
+
+    pub fn nlink(_metadata: &Metadata) -> u64 {
+        1
+    }
+
+    pub fn arg_max() -> Option<usize> {
+        None
+    }
+
+    pub fn kib(metadata: &Metadata) -> u64 {
+        metadata.len().div_ceil(1024)
+    }
+
+    pub fn user_name(_uid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn group_name(_gid: u32) -> Option<String> {
+        None
+    }
+
+    pub fn user_id(_name: &str) -> Option<u32> {
+        None
+    }
+
+    pub fn group_id(_name: &str) -> Option<u32> {
+        None
+    }
+
+    // タイムゾーンを扱えないのでUTCで表す。
+    pub fn local_time(time: SystemTime) -> Tm {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let days = secs.div_euclid(86400);
+        let rem = secs.rem_euclid(86400) as u32;
+
+        // 1970-01-01からの日数をグレゴリオ暦の日付に直す。
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let cumulative = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+        let yday = cumulative[month as usize - 1] + day - 1 + u32::from(leap && month > 2);
+        Tm {
+            year,
+            month,
+            day,
+            hour: rem / 3600,
+            min: rem / 60 % 60,
+            sec: rem % 60,
+            wday: (days + 4).rem_euclid(7) as u32,
+            yday,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{group_id, group_name, user_id, user_name};
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert_eq!(user_id("root"), Some(0));
+        let group = group_name(0).unwrap();
+        assert_eq!(group_id(&group), Some(0));
+        assert_eq!(user_id("no such user"), None);
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    duplicates::Duplicates, expr::Context, ignore::Ignore, summary::Summary, sys, warn_loop,
+    Config, Follow, MyResult,
+};
+use std::{
+    ffi::OsStr,
+    fs::{self, FileType, Metadata},
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+// 式を評価する対象のエントリ。walkdirのDirEntryと同じ使い方ができる。
+#[derive(Debug, Clone)]
+pub struct Entry {
+    path: PathBuf,
+    file_type: FileType,
+    depth: usize,
+    // 辿ったシンボリックリンクか。メタデータはリンク先のものになる。
+    followed: bool,
+}
+
+impl From<walkdir::DirEntry> for Entry {
+    fn from(entry: walkdir::DirEntry) -> Entry {
+        Entry {
+            followed: entry.path_is_symlink() && !entry.file_type().is_symlink(),
+            file_type: entry.file_type(),
+            depth: entry.depth(),
+            path: entry.into_path(),
+        }
+    }
+}
+
+impl Entry {
+    // リンクを辿る場合でも、リンク先がなければfindと同じくリンク自体を返す。
+    pub fn from_path(path: PathBuf, depth: usize, follow: bool) -> io::Result<Entry> {
+        let metadata = fs::symlink_metadata(&path)?;
+        let target = match metadata.file_type().is_symlink() && follow {
+            true => fs::metadata(&path).ok(),
+            false => None,
+        };
+        Ok(Entry {
+            path,
+            followed: target.is_some(),
+            file_type: target.unwrap_or(metadata).file_type(),
+            depth,
+        })
+    }
+
+    // readdirが返す種類を使い、リンクを辿るときだけstatする。
+    fn from_dir_entry(entry: fs::DirEntry, depth: usize, follow: bool) -> io::Result<Entry> {
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() && follow {
+            return Entry::from_path(entry.path(), depth, follow);
+        }
+        Ok(Entry {
+            path: entry.path(),
+            file_type,
+            depth,
+            followed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn path_is_symlink(&self) -> bool {
+        self.followed || self.file_type.is_symlink()
+    }
+
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        match self.followed {
+            true => fs::metadata(&self.path),
+            false => fs::symlink_metadata(&self.path),
+        }
+    }
+}
+
+// 結果の出力先。--sortedでは走査が終わるまで溜めて、パスの順に並べる。
+// --summaryや--duplicatesでは式に一致したエントリを集め、最後に表示する。
+#[derive(Debug, Default)]
+pub struct Output {
+    sorted: bool,
+    buffer: Mutex<Vec<(PathBuf, Vec<u8>)>>,
+    summary: Option<Mutex<Summary>>,
+    duplicates: Option<Mutex<Duplicates>>,
+    json: bool,
+}
+
+impl Output {
+    pub fn new(config: &Config) -> Output {
+        Output {
+            sorted: config.sorted,
+            summary: config
+                .summary
+                .map(|largest| Mutex::new(Summary::new(largest))),
+            duplicates: config.duplicates.then(Default::default),
+            json: config.json,
+            ..Default::default()
+        }
+    }
+
+    pub fn matched(&self, entry: &Entry) {
+        if let Some(summary) = &self.summary {
+            summary.lock().unwrap().add(entry);
+        }
+        if let Some(duplicates) = &self.duplicates {
+            duplicates.lock().unwrap().add(entry);
+        }
+    }
+
+    pub fn write(&self, path: &Path, out: &mut Vec<u8>) -> io::Result<()> {
+        if out.is_empty() {
+            return Ok(());
+        }
+        if self.sorted {
+            let out = mem::take(out);
+            self.buffer.lock().unwrap().push((path.to_path_buf(), out));
+            return Ok(());
+        }
+        let result = io::stdout().lock().write_all(out);
+        out.clear();
+        result
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let mut buffer = self.buffer.into_inner().unwrap();
+        buffer.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut stdout = io::stdout().lock();
+        for (_, out) in buffer {
+            stdout.write_all(&out)?;
+        }
+        if let Some(duplicates) = self.duplicates {
+            duplicates
+                .into_inner()
+                .unwrap()
+                .write(self.json, &mut stdout)?;
+        }
+        match self.summary {
+            Some(summary) => summary.into_inner().unwrap().write(&mut stdout),
+            None => Ok(()),
+        }
+    }
+}
+
+// 1つのディレクトリを読む仕事。
+struct Job {
+    dir: PathBuf,
+    depth: usize,
+    root_dev: u64,
+    // -Lでのループ検出に使う、自身を含む祖先の (パス, 正規化したパス)。
+    ancestors: Vec<(PathBuf, PathBuf)>,
+    ignore: Option<Arc<Ignore>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<Job>,
+    active: usize,
+}
+
+// ディレクトリごとに仕事を分け、threads個のスレッドで並列に走査する。
+// 削除するエントリを返す。
+pub fn parallel(config: &Config, threads: usize, output: &Output) -> MyResult<Vec<PathBuf>> {
+    let walker = Walker {
+        config,
+        output,
+        xdev: config.expr.xdev(),
+        queue: Mutex::new(Queue::default()),
+        ready: Condvar::new(),
+        to_delete: Mutex::new(vec![]),
+        error: Mutex::new(None),
+        failed: AtomicBool::new(false),
+    };
+    for path in &config.paths {
+        match Entry::from_path(PathBuf::from(path), 0, config.follow != Follow::Never) {
+            Ok(entry) => walker.visit(entry, None),
+            Err(e) => io_error(Path::new(path), &e),
+        }
+    }
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| walker.work());
+        }
+    });
+
+    if let Some(e) = walker.error.into_inner().unwrap() {
+        return Err(From::from(e));
+    }
+    Ok(walker.to_delete.into_inner().unwrap())
+}
+
+struct Walker<'a> {
+    config: &'a Config,
+    output: &'a Output,
+    xdev: bool,
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    to_delete: Mutex<Vec<PathBuf>>,
+    error: Mutex<Option<io::Error>>,
+    failed: AtomicBool,
+}
+
+impl Walker<'_> {
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(job) = queue.jobs.pop() {
+                        queue.active += 1;
+                        break job;
+                    }
+                    // 仕事が残っておらず、新しい仕事を積むスレッドもなければ終わり。
+                    if queue.active == 0 {
+                        self.ready.notify_all();
+                        return;
+                    }
+                    queue = self.ready.wait(queue).unwrap();
+                }
+            };
+            self.read_dir(job);
+            let mut queue = self.queue.lock().unwrap();
+            queue.active -= 1;
+            if queue.active == 0 && queue.jobs.is_empty() {
+                self.ready.notify_all();
+            }
+        }
+    }
+
+    fn read_dir(&self, job: Job) {
+        let entries = match fs::read_dir(&job.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                io_error(&job.dir, &e);
+                return;
+            }
+        };
+        let follow = self.config.follow == Follow::Always;
+        for entry in entries {
+            let entry = entry.and_then(|entry| Entry::from_dir_entry(entry, job.depth + 1, follow));
+            match entry {
+                Ok(entry) => self.visit(entry, Some(&job)),
+                Err(e) => io_error(&job.dir, &e),
+            }
+            if self.failed.load(Ordering::Relaxed) {
+                return;
+            }
+        }
+    }
+
+    // 式を評価し、ディレクトリなら子を読む仕事を積む。parentはエントリを含むディレクトリ。
+    fn visit(&self, entry: Entry, parent: Option<&Job>) {
+        if is_excluded(
+            self.config,
+            &entry,
+            parent.and_then(|job| job.ignore.as_deref()),
+        ) {
+            return;
+        }
+
+        // -Lで祖先を指すリンクは、walkdirと同じく評価せずに警告する。
+        let mut ancestors = match (parent, entry.file_type().is_dir()) {
+            (Some(job), true) => job.ancestors.clone(),
+            _ => vec![],
+        };
+        if self.config.follow == Follow::Always && entry.file_type().is_dir() {
+            let canonical = match entry.path().canonicalize() {
+                Ok(canonical) => canonical,
+                Err(e) => return io_error(entry.path(), &e),
+            };
+            let ancestor = ancestors.iter().find(|(_, c)| *c == canonical);
+            if let Some((ancestor, _)) = ancestor.filter(|_| entry.path_is_symlink()) {
+                return warn_loop(entry.path(), ancestor);
+            }
+            ancestors.push((entry.path().to_path_buf(), canonical));
+        }
+
+        let mut ctx = Context::default();
+        if self
+            .config
+            .min_depth
+            .is_none_or(|depth| entry.depth() >= depth)
+        {
+            if self.config.expr.eval(&entry, &mut ctx) {
+                self.output.matched(&entry);
+            }
+            if let Err(e) = self.output.write(entry.path(), &mut ctx.out) {
+                self.error.lock().unwrap().get_or_insert(e);
+                self.failed.store(true, Ordering::Relaxed);
+                return;
+            }
+            if !ctx.delete.is_empty() {
+                self.to_delete.lock().unwrap().append(&mut ctx.delete);
+            }
+        }
+
+        if ctx.prune
+            || !entry.file_type().is_dir()
+            || self
+                .config
+                .max_depth
+                .is_some_and(|depth| entry.depth() >= depth)
+        {
+            return;
+        }
+        let root_dev = match parent {
+            Some(job) => job.root_dev,
+            None => entry.metadata().map(|m| sys::dev(&m)).unwrap_or(0),
+        };
+        if self.xdev && entry.depth() > 0 {
+            match entry.metadata() {
+                Ok(metadata) if sys::dev(&metadata) == root_dev => {}
+                _ => return,
+            }
+        }
+        let ignore = match parent {
+            _ if !self.config.gitignore => None,
+            Some(job) => job.ignore.as_ref().map(|ignore| ignore.child(entry.path())),
+            None => Some(Ignore::root(entry.path())),
+        };
+        let mut queue = self.queue.lock().unwrap();
+        queue.jobs.push(Job {
+            dir: entry.path,
+            depth: entry.depth,
+            root_dev,
+            ancestors,
+            ignore,
+        });
+        self.ready.notify_one();
+    }
+}
+
+// --no-hiddenや--respect-gitignoreで除外するエントリか。起点は除外しない。
+// 除外したディレクトリの中は走査しない。.git もワークツリーではないので除外する。
+pub fn is_excluded(config: &Config, entry: &Entry, ignore: Option<&Ignore>) -> bool {
+    if entry.depth() == 0 {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy();
+    (config.no_hidden && name.starts_with('.'))
+        || (config.gitignore && name == ".git")
+        || ignore.is_some_and(|ignore| ignore.is_ignored(entry.path(), entry.file_type().is_dir()))
+}
+
+// walkdirのエラーと同じ形式で表示する。
+fn io_error(path: &Path, e: &io::Error) {
+    eprintln!("IO error for operation on {}: {}", path.display(), e);
+}
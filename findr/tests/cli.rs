@@ -1,7 +1,12 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{borrow::Cow, fs, path::Path};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -222,6 +227,19 @@ fn type_f_l() -> TestResult {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn type_f_l_list() -> TestResult {
+    run(
+        &["tests/inputs", "-t", "l,f"],
+        "tests/expected/type_f_l.txt",
+    )?;
+    run(
+        &["tests/inputs", "-type", "l,f"],
+        "tests/expected/type_f_l.txt",
+    )
+}
+
 // --------------------------------------------------
 #[test]
 fn name_csv() -> TestResult {
@@ -231,6 +249,16 @@ fn name_csv() -> TestResult {
     )
 }
 
+// --------------------------------------------------
+#[test]
+fn name_csv_attached() -> TestResult {
+    run(
+        &["tests/inputs", "-n.*[.]csv"],
+        "tests/expected/name_csv.txt",
+    )?;
+    run(&["tests/inputs", "-ncsv"], "tests/expected/name_csv.txt")
+}
+
 // --------------------------------------------------
 #[test]
 fn name_csv_mp3() -> TestResult {
@@ -313,3 +341,1215 @@ fn unreadable_dir() -> TestResult {
     assert!(stderr.contains("cant-touch-this: Permission denied"));
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_max_depth() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--max-depth", "x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid --max-depth \"x\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_0() -> TestResult {
+    run(
+        &["tests/inputs", "--max-depth", "0"],
+        "tests/expected/max_depth_0.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn max_depth_1() -> TestResult {
+    run(
+        &["tests/inputs", "--max-depth", "1"],
+        "tests/expected/max_depth_1.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_3() -> TestResult {
+    run(
+        &["tests/inputs", "--min-depth", "3"],
+        "tests/expected/min_depth_3.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn min_depth_1_max_depth_1_type_f() -> TestResult {
+    run(
+        &["tests/inputs", "--min-depth", "1", "--max-depth", "1", "-t", "f"],
+        "tests/expected/min_depth_1_max_depth_1_type_f.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expression() -> TestResult {
+    for (args, expected) in [
+        (vec!["-name"], "missing argument to \"-name\""),
+        (vec!["-foo"], "unknown predicate \"-foo\""),
+        (vec!["(", "-name", "a"], "missing \")\""),
+        (vec!["-name", "a", "-o"], "expected an expression after \"-o\""),
+        (vec!["-type", "x"], "invalid -type \"x\""),
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_csv_or_mp3_not_path_a_b() -> TestResult {
+    run(
+        &["tests/inputs", "(", "-name", "*.csv", "-o", "-name", "*.mp3", ")", "!", "-path", "*/a/b/*"],
+        "tests/expected/expr_csv_or_mp3_not_path_a_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_f_txt_or_type_d_a_e() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "f", "-name", "*.txt", "-o", "-type", "d", "-a", "-name", "[ae]"],
+        "tests/expected/expr_type_f_txt_or_type_d_a_e.txt",
+    )
+}
+
+// --------------------------------------------------
+// 日時や大きさの異なるファイルを一時ディレクトリに作る。
+fn gen_tree(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+    fs::create_dir_all(root.join("empty_dir"))?;
+    fs::write(root.join("old.txt"), "x".repeat(100))?;
+    fs::write(root.join("new.txt"), "x".repeat(3000))?;
+    fs::write(root.join("empty.txt"), "")?;
+
+    let ten_days_ago = SystemTime::now() - Duration::from_secs(10 * 24 * 60 * 60);
+    fs::File::options()
+        .write(true)
+        .open(root.join("old.txt"))?
+        .set_modified(ten_days_ago)?;
+    Ok(root)
+}
+
+// --------------------------------------------------
+fn run_tree(root: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    let mut expected: Vec<String> = expected
+        .iter()
+        .map(|name| root.join(name).display().to_string())
+        .collect();
+    expected.sort();
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(root)
+        .args(args)
+        .assert()
+        .success();
+    let out = cmd.get_output();
+    let stdout = String::from_utf8(out.stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.split('\n').filter(|s| !s.is_empty()).collect();
+    lines.sort();
+
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn attached_short_option_values() -> TestResult {
+    let root = gen_tree("attached_short_option_values")?;
+    let files = ["empty.txt", "new.txt", "old.txt"];
+    run_tree(&root, &["-tf"], &files)?;
+    run_tree(&root, &["-j4", "-tf"], &files)?;
+    run_tree(&root, &["-j4", "-nnew"], &["new.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn size() -> TestResult {
+    let root = gen_tree("size")?;
+    run_tree(&root, &["-type", "f", "-size", "+2k"], &["new.txt"])?;
+    run_tree(&root, &["-type", "f", "-size", "-1k"], &["empty.txt"])?;
+    run_tree(&root, &["-type", "f", "-size", "100c"], &["old.txt"])?;
+    run_tree(&root, &["-type", "f", "-size", "+1"], &["new.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn mtime() -> TestResult {
+    let root = gen_tree("mtime")?;
+    run_tree(&root, &["-mtime", "+5"], &["old.txt"])?;
+    run_tree(&root, &["-type", "f", "-mtime", "-1"], &["new.txt", "empty.txt"])?;
+    run_tree(&root, &["-type", "f", "-mmin", "+60"], &["old.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn newer() -> TestResult {
+    let root = gen_tree("newer")?;
+    let old = root.join("old.txt").display().to_string();
+    run_tree(
+        &root,
+        &["-type", "f", "-newer", &old],
+        &["new.txt", "empty.txt"],
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    let root = gen_tree("empty")?;
+    run_tree(&root, &["-empty"], &["empty.txt", "empty_dir"])?;
+    run_tree(&root, &["-empty", "-type", "d"], &["empty_dir"])
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_newer() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args(["-newer", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(format!("{}: .* [(]os error 2[)]", bad))?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_each() -> TestResult {
+    let root = gen_tree("exec_each")?;
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["-type", "f", "-exec", "echo", "file:{}", ";"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    let expected: Vec<String> = ["empty.txt", "new.txt", "old.txt"]
+        .iter()
+        .map(|name| format!("file:{}", root.join(name).display()))
+        .collect();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_batch() -> TestResult {
+    let root = gen_tree("exec_batch")?;
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["-type", "f", "-exec", "echo", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout.lines().count(), 1);
+    assert_eq!(stdout.split_whitespace().count(), 3);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn execdir() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "c.mp3", "-execdir", "ls", "{}", ";"])
+        .assert()
+        .success()
+        .stdout("./c.mp3\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_print_order() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "-print", "-exec", "echo", "done", ";"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/g.csv\ndone\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_sorted_parallel() -> TestResult {
+    let root = gen_tree("exec_sorted_parallel")?;
+    let expected: String = ["empty.txt", "new.txt", "old.txt"]
+        .iter()
+        .map(|name| format!("{}\nfile:./{}\n", root.join(name).display(), name))
+        .collect();
+    for threads in ["1", "4"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", threads, "--sorted"])
+            .arg(&root)
+            .args(["-type", "f", "-print", "-execdir", "echo", "file:{}", ";"])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_failed() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "-exec", "false", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("one or more commands failed"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn ok_prompt() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "-ok", "echo", "yes", "{}", ";"])
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stdout("yes tests/inputs/g.csv\n")
+        .stderr("< echo yes tests/inputs/g.csv > ? ");
+
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/g.csv", "-ok", "echo", "yes", "{}", ";"])
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn prune_b() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "b", "-prune", "-o", "-print"],
+        "tests/expected/prune_b.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn delete() -> TestResult {
+    let root = gen_tree("delete")?;
+    run_tree(&root, &["-name", "old*", "-delete"], &[])?;
+    run_tree(
+        &root,
+        &["--min-depth", "1"],
+        &["empty.txt", "empty_dir", "new.txt"],
+    )?;
+
+    // 空でないディレクトリは削除できない
+    Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["-type", "d", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("one or more entries could not be deleted"));
+    run_tree(&root, &["--min-depth", "1"], &["empty.txt", "new.txt"])
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_dot() -> TestResult {
+    let root = gen_tree("delete_dot")?;
+    Command::cargo_bin(PRG)?
+        .arg(root.join("."))
+        .args(["-name", "*", "-delete"])
+        .assert()
+        .success()
+        .stderr("");
+    assert!(root.is_dir());
+    assert_eq!(fs::read_dir(&root)?.count(), 0);
+
+    // 起点がそのまま . のとき
+    fs::write(root.join("a.txt"), "a")?;
+    Command::cargo_bin(PRG)?
+        .current_dir(&root)
+        .args([".", "-name", "*", "-delete"])
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(fs::read_dir(&root)?.count(), 0);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn delete_dry_run() -> TestResult {
+    let root = gen_tree("delete_dry_run")?;
+    Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["--dry-run", "-name", "old.*", "-delete"])
+        .assert()
+        .success()
+        .stdout(format!("would delete {}\n", root.join("old.txt").display()));
+    assert!(root.join("old.txt").exists());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_first() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-delete", "-name", "a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("\"-delete\" must follow the tests"));
+
+    // テストより前に評価される-deleteは、位置によらず拒む
+    for args in [
+        vec!["(", "-delete", ")", "-name", "a"],
+        vec!["-true", "-delete", "-name", "a"],
+        vec!["-delete"],
+        vec!["-print", "-o", "-delete"],
+    ] {
+        Command::cargo_bin(PRG)?
+            .args(["tests/inputs", "--dry-run"])
+            .args(&args)
+            .assert()
+            .failure()
+            .stdout("")
+            .stderr(predicate::str::contains("\"-delete\" must follow the tests"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn delete_refuses_root() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["/", "--max-depth", "0", "--dry-run"])
+        .args(["-type", "d", "-delete"])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains("/: refusing to delete the root directory"));
+
+    // / の中身も、走査を始める前に拒むので何も削除しない
+    for root in ["/", "/.."] {
+        Command::cargo_bin(PRG)?
+            .args([root, "--max-depth", "1", "--dry-run"])
+            .args(["-name", "*", "-delete"])
+            .assert()
+            .failure()
+            .stdout("")
+            .stderr(format!("{}: refusing to delete the root directory\n", root));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn iname_csv() -> TestResult {
+    run(
+        &["tests/inputs", "-iname", "*.CSV"],
+        "tests/expected/iname_csv.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn regex_a_d_txt() -> TestResult {
+    run(
+        &["tests/inputs", "-regex", ".*/[ad]/[a-d]\\.t.t"],
+        "tests/expected/regex_a_d_txt.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn ipath_d() -> TestResult {
+    run(&["tests/inputs", "-ipath", "*/D/*"], "tests/expected/ipath_d.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn printf_f_d_y_s_h() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "f", "-printf", "%f %d %y %s %h\\n"],
+        "tests/expected/printf_f_d_y_s_h.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn print0() -> TestResult {
+    let expected = format!("{}\0", Path::new("tests/inputs").join("g.csv").display());
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "g.csv", "-print0"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn ls() -> TestResult {
+    let expected = r"^ *\d+ +\d+ -[-rwxsStT]{9} +1 +\S+ +\S+ +2 [A-Z][a-z]{2} [ \d]\d ( \d{4}|\d\d:\d\d) tests/inputs.g\.csv\n$";
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-name", "g.csv", "-ls"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_printf() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", "-printf", "%z"])
+        .assert()
+        .failure()
+        .stderr("invalid -printf directive \"%z\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn perm() -> TestResult {
+    use std::os::unix::fs::PermissionsExt;
+    let root = gen_tree("perm")?;
+    for (name, mode) in [("old.txt", 0o644), ("new.txt", 0o666), ("empty.txt", 0o4755)] {
+        fs::set_permissions(root.join(name), fs::Permissions::from_mode(mode))?;
+    }
+    let args = ["-type", "f", "-perm"];
+    run_tree(&root, &[&args[..], &["644"]].concat(), &["old.txt"])?;
+    run_tree(&root, &[&args[..], &["u=rw,go=r"]].concat(), &["old.txt"])?;
+    run_tree(&root, &[&args[..], &["-o+w"]].concat(), &["new.txt"])?;
+    run_tree(
+        &root,
+        &[&args[..], &["-444"]].concat(),
+        &["old.txt", "new.txt", "empty.txt"],
+    )?;
+    run_tree(&root, &[&args[..], &["/u+s,o+w"]].concat(), &["new.txt", "empty.txt"])?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn owner() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+    let root = gen_tree("owner")?;
+    let metadata = fs::metadata(root.join("old.txt"))?;
+    let (uid, gid) = (metadata.uid().to_string(), metadata.gid().to_string());
+    let all = ["old.txt", "new.txt", "empty.txt"];
+    run_tree(&root, &["-type", "f", "-uid", &uid], &all)?;
+    run_tree(&root, &["-type", "f", "-user", &uid, "-group", &gid], &all)?;
+    run_tree(&root, &["-type", "f", "-gid", &format!("+{}", gid)], &[])?;
+    run_tree(&root, &["-type", "f", "-nouser"], &[])?;
+
+    Command::cargo_bin(PRG)?
+        .args([".", "-user", "no such user"])
+        .assert()
+        .failure()
+        .stderr("\"no such user\" is not the name of a known user\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn inode() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+    let root = gen_tree("inode")?;
+    fs::hard_link(root.join("old.txt"), root.join("link.txt"))?;
+    let ino = fs::metadata(root.join("old.txt"))?.ino().to_string();
+    run_tree(&root, &["-inum", &ino], &["old.txt", "link.txt"])?;
+    run_tree(&root, &["-type", "f", "-links", "+1"], &["old.txt", "link.txt"])?;
+    run_tree(
+        &root,
+        &["-samefile", &root.join("link.txt").display().to_string()],
+        &["old.txt", "link.txt"],
+    )?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+fn gen_links(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::symlink;
+    let root = gen_tree(name)?;
+    fs::create_dir(root.join("dir"))?;
+    fs::write(root.join("dir").join("inner.txt"), "")?;
+    symlink("dir", root.join("dir_link"))?;
+    symlink("old.txt", root.join("file_link"))?;
+    symlink("missing", root.join("broken_link"))?;
+    Ok(root)
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_links() -> TestResult {
+    let root = gen_links("follow_links")?;
+    let links = ["dir_link", "file_link", "broken_link"];
+    run_tree(&root, &["-type", "l"], &links)?;
+    run_tree(&root, &["-name", "inner.txt"], &["dir/inner.txt"])?;
+
+    // -Lではリンク先の種類になり、壊れたリンクだけが -type l になる
+    let args = ["-L", root.to_str().unwrap()];
+    let mut cmd = Command::cargo_bin(PRG)?;
+    let out = cmd.args(args).args(["-type", "l"]).output()?;
+    assert_eq!(
+        String::from_utf8(out.stdout)?,
+        format!("{}\n", root.join("broken_link").display())
+    );
+    let out = Command::cargo_bin(PRG)?
+        .args(args)
+        .args(["-name", "inner.txt"])
+        .output()?;
+    let mut lines: Vec<String> = String::from_utf8(out.stdout)?
+        .lines()
+        .map(String::from)
+        .collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            root.join("dir/inner.txt").display().to_string(),
+            root.join("dir_link/inner.txt").display().to_string(),
+        ]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_roots() -> TestResult {
+    let root = gen_links("follow_roots")?;
+    let link = root.join("dir_link");
+    let link = link.to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([link, "-printf", "%y %f\\n"])
+        .assert()
+        .success()
+        .stdout("l dir_link\n");
+    Command::cargo_bin(PRG)?
+        .args(["-H", link, "-printf", "%y %f\\n"])
+        .assert()
+        .success()
+        .stdout("d dir_link\nf inner.txt\n");
+    Command::cargo_bin(PRG)?
+        .args(["-L", "-P", link])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", link));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn follow_loop() -> TestResult {
+    let root = gen_links("follow_loop")?;
+    std::os::unix::fs::symlink("..", root.join("dir").join("up"))?;
+    Command::cargo_bin(PRG)?
+        .args(["-L", root.to_str().unwrap(), "-name", "up"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains(format!(
+            "{}: file system loop detected; it points to its ancestor {}\n",
+            root.join("dir/up").display(),
+            root.display()
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev() -> TestResult {
+    run(&["tests/inputs", "-xdev"], "tests/expected/path1.txt")?;
+    run(&["tests/inputs", "-mount"], "tests/expected/path1.txt")
+}
+
+// --------------------------------------------------
+#[test]
+fn parallel() -> TestResult {
+    let cases: &[(&[&str], &str)] = &[
+        (&["tests/inputs"], "tests/expected/path1.txt"),
+        (&["tests/inputs/a"], "tests/expected/path_a.txt"),
+        (&["tests/inputs", "--max-depth", "0"], "tests/expected/max_depth_0.txt"),
+        (&["tests/inputs", "--min-depth", "3"], "tests/expected/min_depth_3.txt"),
+        (&["tests/inputs", "-name", "*.csv"], "tests/expected/name_csv.txt"),
+        (
+            &["tests/inputs", "-name", "b", "-prune", "-o", "-print"],
+            "tests/expected/prune_b.txt",
+        ),
+        (
+            &["tests/inputs", "-type", "f", "-printf", "%f %d %y %s %h\\n"],
+            "tests/expected/printf_f_d_y_s_h.txt",
+        ),
+    ];
+    for (args, expected) in cases {
+        run(&[&["-j", "4"], *args].concat(), expected)?;
+        run(&[&["-j3", "--sorted"], *args].concat(), expected)?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sorted() -> TestResult {
+    let mut expected = fs::read_to_string(format_file_name("tests/expected/path1.txt").as_ref())?
+        .lines()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    expected.sort();
+    let expected: String = expected
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect();
+    for threads in ["1", "4"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", threads, "--sorted", "tests/inputs"])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn parallel_follow_links() -> TestResult {
+    let root = gen_links("parallel_follow_links")?;
+    std::os::unix::fs::symlink("..", root.join("dir").join("up"))?;
+    let root = root.to_str().unwrap();
+    for args in [vec![root], vec!["-L", root], vec!["-H", root]] {
+        let sequential = Command::cargo_bin(PRG)?
+            .args(&args)
+            .args(["--sorted", "-printf", "%y %d %p\\n"])
+            .output()?;
+        let parallel = Command::cargo_bin(PRG)?
+            .args(["-j", "4"])
+            .args(&args)
+            .args(["--sorted", "-printf", "%y %d %p\\n"])
+            .output()?;
+        assert_eq!(
+            String::from_utf8(parallel.stdout)?,
+            String::from_utf8(sequential.stdout)?
+        );
+        let mut sequential: Vec<_> = sequential.stderr.split(|b| *b == b'\n').collect();
+        let mut parallel: Vec<_> = parallel.stderr.split(|b| *b == b'\n').collect();
+        sequential.sort();
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn gen_repo(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    if root.exists() {
+        fs::remove_dir_all(&root)?;
+    }
+    for dir in [".git/info", "src", "target/debug", "sub/deep", ".hidden", "home"] {
+        fs::create_dir_all(root.join(dir))?;
+    }
+    let files = [
+        (".gitignore", "target/\n*.log\n!keep.log\n"),
+        (".ignore", "secret.txt\n"),
+        (".git/info/exclude", "info.txt\n"),
+        ("sub/.gitignore", "/deep\n"),
+        ("home/.gitconfig", "[core]\n\texcludesFile = ~/global_ignore\n"),
+        ("home/global_ignore", "*.rs\nhome/\n"),
+    ];
+    for (file, contents) in files {
+        fs::write(root.join(file), contents)?;
+    }
+    for file in [
+        "src/main.rs",
+        "target/debug/app",
+        "a.log",
+        "keep.log",
+        "secret.txt",
+        "info.txt",
+        "sub/deep/d.txt",
+        "sub/s.txt",
+        ".hidden/h.txt",
+    ] {
+        fs::write(root.join(file), "")?;
+    }
+    Ok(root)
+}
+
+// --------------------------------------------------
+#[test]
+fn respect_gitignore() -> TestResult {
+    let root = gen_repo("respect_gitignore")?;
+    let expected = [
+        "",
+        ".gitignore",
+        ".ignore",
+        ".hidden",
+        ".hidden/h.txt",
+        "keep.log",
+        "src",
+        "sub",
+        "sub/.gitignore",
+        "sub/s.txt",
+    ];
+    let mut expected: Vec<String> = expected
+        .iter()
+        .map(|name| root.join(name).display().to_string())
+        .collect();
+    expected[0] = root.display().to_string();
+    expected.sort();
+    let no_hidden: Vec<&String> = expected
+        .iter()
+        .filter(|path| !path.contains(&format!("{}.", std::path::MAIN_SEPARATOR)))
+        .collect();
+
+    for threads in ["1", "3"] {
+        for (flags, expected) in [
+            (&["--respect-gitignore"][..], expected.iter().collect()),
+            (&["--respect-gitignore", "--no-hidden"], no_hidden.clone()),
+        ] {
+            let out = Command::cargo_bin(PRG)?
+                .env("HOME", root.join("home"))
+                .env_remove("XDG_CONFIG_HOME")
+                .args(["-j", threads])
+                .args(flags)
+                .arg(&root)
+                .output()?;
+            let stdout = String::from_utf8(out.stdout)?;
+            let mut lines: Vec<&str> = stdout.lines().collect();
+            lines.sort();
+            assert_eq!(lines, expected);
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn global_excludes_xdg() -> TestResult {
+    let root = gen_repo("global_excludes_xdg")?;
+    let xdg = root.join("home").join(".config");
+    fs::create_dir_all(xdg.join("git"))?;
+    let rs_files = |home: &Path| -> Result<String, Box<dyn std::error::Error>> {
+        let out = Command::cargo_bin(PRG)?
+            .env("HOME", home)
+            .env_remove("XDG_CONFIG_HOME")
+            .arg(&root)
+            .args(["--respect-gitignore", "-name", "*.rs"])
+            .output()?;
+        Ok(String::from_utf8(out.stdout)?)
+    };
+    let main_rs = format!("{}\n", root.join("src").join("main.rs").display());
+    fs::remove_file(root.join("home/.gitconfig"))?;
+    assert_eq!(rs_files(&root.join("home"))?, main_rs);
+
+    // 設定がなければ $XDG_CONFIG_HOME/git/ignore を使う
+    fs::write(xdg.join("git/ignore"), "*.rs\n")?;
+    assert_eq!(rs_files(&root.join("home"))?, "");
+    fs::remove_file(xdg.join("git/ignore"))?;
+
+    // $XDG_CONFIG_HOME/git/config の core.excludesFile
+    fs::write(
+        xdg.join("git/config"),
+        "[core]\n\texcludesFile = ~/global_ignore\n",
+    )?;
+    assert_eq!(rs_files(&root.join("home"))?, "");
+
+    // ~/.gitconfig の設定を優先する
+    fs::write(root.join("home/other_ignore"), "*.txt\n")?;
+    fs::write(
+        root.join("home/.gitconfig"),
+        "[core]\n\texcludesFile = ~/other_ignore\n",
+    )?;
+    assert_eq!(rs_files(&root.join("home"))?, main_rs);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_hidden() -> TestResult {
+    let root = gen_repo("no_hidden")?;
+    run_tree(
+        &root,
+        &["--min-depth", "1", "-name", "*.txt"],
+        &[
+            "secret.txt",
+            "info.txt",
+            "sub/deep/d.txt",
+            "sub/s.txt",
+            ".hidden/h.txt",
+        ],
+    )?;
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--no-hidden"])
+        .arg(&root)
+        .args(["-name", "*.txt"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    let mut expected: Vec<String> = ["secret.txt", "info.txt", "sub/deep/d.txt", "sub/s.txt"]
+        .iter()
+        .map(|name| root.join(name).display().to_string())
+        .collect();
+    expected.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_name() -> TestResult {
+    // ディレクトリごとに名前で並べると、パス全体で並べたものと同じ順になる。
+    let mut expected = fs::read_to_string(format_file_name("tests/expected/path1.txt").as_ref())?
+        .lines()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    expected.sort();
+    let expected: String = expected
+        .iter()
+        .map(|path| format!("{}\n", path.display()))
+        .collect();
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "name", "tests/inputs"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_sorted(root: &Path, args: &[&str], expected: &[&str]) -> TestResult {
+    let expected: String = expected
+        .iter()
+        .map(|name| format!("{}\n", root.join(name).display()))
+        .collect();
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .arg(root)
+        .args(["-type", "f"])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sort_size_mtime() -> TestResult {
+    let root = gen_tree("sort_size_mtime")?;
+    let five_days_ago = SystemTime::now() - Duration::from_secs(5 * 24 * 60 * 60);
+    fs::File::options()
+        .write(true)
+        .open(root.join("empty.txt"))?
+        .set_modified(five_days_ago)?;
+
+    let by_size = ["empty.txt", "old.txt", "new.txt"];
+    run_sorted(&root, &["--sort=size"], &by_size)?;
+    let mut reversed = by_size;
+    reversed.reverse();
+    run_sorted(&root, &["--sort=size", "--reverse"], &reversed)?;
+    run_sorted(
+        &root,
+        &["--sort=mtime"],
+        &["old.txt", "empty.txt", "new.txt"],
+    )?;
+    run_sorted(
+        &root,
+        &["--sort=name", "--reverse"],
+        &["old.txt", "new.txt", "empty.txt"],
+    )?;
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_sort() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "age"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("isn't a valid value"));
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "name", "-j", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--sort cannot be used with --threads",
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["--reverse"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn xtype() -> TestResult {
+    let root = gen_links("xtype")?;
+    run_tree(&root, &["-xtype", "l"], &["broken_link"])?;
+    run_tree(&root, &["-xtype", "d", "-name", "*link"], &["dir_link"])?;
+    run_tree(
+        &root,
+        &["-type", "l", "-xtype", "f,d"],
+        &["dir_link", "file_link"],
+    )?;
+
+    // -Lでは辿ったリンクがリンクとして扱われる
+    let root = root.to_str().unwrap();
+    let out = Command::cargo_bin(PRG)?
+        .args(["-L", root, "-xtype", "l", "-name", "*link"])
+        .output()?;
+    let stdout = String::from_utf8(out.stdout)?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    let mut expected: Vec<String> = ["broken_link", "dir_link", "file_link"]
+        .iter()
+        .map(|name| Path::new(root).join(name).display().to_string())
+        .collect();
+    expected.sort();
+    assert_eq!(lines, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn type_special() -> TestResult {
+    let root = gen_tree("type_special")?;
+    let fifo = std::ffi::CString::new(root.join("fifo").to_str().unwrap())?;
+    assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+    let _socket = std::os::unix::net::UnixListener::bind(root.join("socket"))?;
+
+    run_tree(&root, &["-type", "p"], &["fifo"])?;
+    run_tree(&root, &["-type", "s"], &["socket"])?;
+    run_tree(&root, &["-type", "p,s"], &["fifo", "socket"])?;
+    run_tree(&root, &["-type", "b,c"], &[])?;
+    Command::cargo_bin(PRG)?
+        .args(["--sort", "name"])
+        .arg(&root)
+        .args(["-type", "p,s", "-printf", "%y\\n"])
+        .assert()
+        .success()
+        .stdout("p\ns\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+// 内容の異なるファイルを一時ディレクトリに作る。
+fn gen_contents(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let root = gen_tree(name)?;
+    fs::write(root.join("main.rs"), "fn main() {\n    todo!()\n}\n")?;
+    fs::write(root.join("lib.rs"), "pub fn lib() {}\n")?;
+    fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR")?;
+    fs::write(root.join("data.gz"), b"\x1f\x8b\x08\0\0\0\0\0")?;
+    fs::write(root.join("blob.bin"), b"\0\x01\x02todo!()")?;
+    Ok(root)
+}
+
+// --------------------------------------------------
+#[test]
+fn contains() -> TestResult {
+    let root = gen_contents("contains")?;
+    run_tree(&root, &["-contains", "todo!"], &["main.rs", "blob.bin"])?;
+    run_tree(&root, &["-contains", "^pub fn"], &["lib.rs"])?;
+    run_tree(&root, &["-contains", "^x+$"], &["old.txt", "new.txt"])?;
+    run_tree(
+        &root,
+        &["-name", "*.rs", "!", "-contains", "todo"],
+        &["lib.rs"],
+    )?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["--contains", "(?i)TODO", "--name", "rs$"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout, format!("{}\n", root.join("main.rs").display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mime() -> TestResult {
+    let root = gen_contents("mime")?;
+    run_tree(&root, &["-mime", "image/png"], &["logo.png"])?;
+    run_tree(&root, &["-mime", "image"], &["logo.png"])?;
+    run_tree(&root, &["-mime", "application/gzip"], &["data.gz"])?;
+    run_tree(
+        &root,
+        &["-mime", "text/*"],
+        &["main.rs", "lib.rs", "old.txt", "new.txt"],
+    )?;
+    run_tree(&root, &["-mime", "inode/x-empty"], &["empty.txt"])?;
+    run_tree(&root, &["-mime", "application/octet-stream"], &["blob.bin"])?;
+
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--mime", "application/gzip"])
+        .arg(&root)
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    assert_eq!(stdout, format!("{}\n", root.join("data.gz").display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_contains() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--contains", "(a"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid -contains \"(a\""));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn summary() -> TestResult {
+    let root = gen_tree("summary")?;
+    fs::create_dir(root.join("empty_dir").join("deep"))?;
+    let expected = format!(
+        "6 entries: 3 directories, 3 files
+total size of files: 3100 bytes
+largest files:
+        3000  {}
+         100  {}
+deepest path: {} (depth 2)
+",
+        root.join("new.txt").display(),
+        root.join("old.txt").display(),
+        root.join("empty_dir").join("deep").display(),
+    );
+    for threads in ["1", "4"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", threads, "--summary", "--largest", "2"])
+            .arg(&root)
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+
+    // アクションがあれば一覧の後に表示する
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--summary"])
+        .arg(&root)
+        .args(["-name", "old.txt", "-print"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let old = root.join("old.txt").display().to_string();
+    assert_eq!(
+        stdout,
+        format!(
+            "{}\n1 entries: 1 files\ntotal size of files: 100 bytes\nlargest files:\n         100  {}\ndeepest path: {} (depth 1)\n",
+            old, old, old
+        )
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn duplicates() -> TestResult {
+    let root = gen_tree("duplicates")?;
+    fs::create_dir(root.join("copies"))?;
+    let big = "x".repeat(10000);
+    let files = [
+        ("hello.txt", "hello".to_string()),
+        ("copies/hello.txt", "hello".to_string()),
+        ("hellp.txt", "hellp".to_string()),
+        ("big1", format!("{}a", big)),
+        ("copies/big1", format!("{}a", big)),
+        ("copies/big2", format!("{}a", big)),
+        ("big3", format!("{}b", big)),
+        ("copies/empty.txt", String::new()),
+    ];
+    for (file, contents) in files {
+        fs::write(root.join(file), contents)?;
+    }
+    // ハードリンクは同じファイルなので重複としない
+    if cfg!(unix) {
+        fs::hard_link(root.join("hello.txt"), root.join("z_link.txt"))?;
+        fs::hard_link(root.join("hellp.txt"), root.join("copies/hellp.txt"))?;
+    }
+    let path = |name: &str| root.join(name).display().to_string();
+
+    let expected = format!(
+        "{}\n{}\n{}\n\n{}\n{}\n",
+        path("big1"),
+        path("copies/big1"),
+        path("copies/big2"),
+        path("copies/hello.txt"),
+        path("hello.txt"),
+    );
+    for threads in ["1", "4"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", threads, "--duplicates"])
+            .arg(&root)
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+
+    // 式で絞り込んだファイルだけを比べる
+    let json = |path: String| path.replace('\\', "\\\\");
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates", "--json"])
+        .arg(&root)
+        .args(["-name", "*.txt"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "[\n  {{\"size\": 5, \"paths\": [\"{}\", \"{}\"]}}\n]\n",
+            json(path("copies/hello.txt")),
+            json(path("hello.txt")),
+        ));
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates", "--json"])
+        .arg(&root)
+        .args(["-name", "*.csv"])
+        .assert()
+        .success()
+        .stdout("[]\n");
+    Ok(())
+}
@@ -1,23 +1,110 @@
 use clap::{App, Arg};
+use encoding_rs::Encoding;
+use fancy_regex::Regex as FancyRegex;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use memmap2::Mmap;
 use regex::{Regex, RegexBuilder};
 use std::{
+    cell::Cell,
     error::Error,
+    fmt::Debug,
     fs::{self, File},
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read},
     mem,
+    rc::Rc,
+    str,
+    time::{Duration, Instant},
 };
 use walkdir::{DirEntry, WalkDir};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
+// Autoの場合、これ以上の大きさの通常ファイルをmmapで検索する。
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum MmapChoice {
+    Auto,
+    Always,
+    Never,
+}
+
 #[derive(Debug)]
 pub struct Config {
-    pattern: Regex,
+    pattern: Box<dyn Matcher>,
     files: Vec<String>,
     ignore_case: bool,
     invert: bool,
     counts: bool,
     recursive: bool,
+    encoding: Option<&'static Encoding>,
+    mmap: MmapChoice,
+    count_matches: bool,
+    stats: bool,
+    pcre: bool,
+    files_with_matches: bool,
+    null: bool,
+    null_data: bool,
+}
+
+// 正規表現エンジンの共通インターフェース。
+trait Matcher: Debug {
+    fn as_str(&self) -> &str;
+    fn is_match(&self, text: &str) -> MyResult<bool>;
+    fn count(&self, text: &str) -> MyResult<usize>;
+}
+
+impl Matcher for Regex {
+    fn as_str(&self) -> &str {
+        Regex::as_str(self)
+    }
+
+    fn is_match(&self, text: &str) -> MyResult<bool> {
+        Ok(Regex::is_match(self, text))
+    }
+
+    fn count(&self, text: &str) -> MyResult<usize> {
+        Ok(self.find_iter(text).count())
+    }
+}
+
+// 先読み・後読み・後方参照に対応したバックトラック型のエンジン。
+impl Matcher for FancyRegex {
+    fn as_str(&self) -> &str {
+        FancyRegex::as_str(self)
+    }
+
+    fn is_match(&self, text: &str) -> MyResult<bool> {
+        Ok(FancyRegex::is_match(self, text)?)
+    }
+
+    fn count(&self, text: &str) -> MyResult<usize> {
+        self.find_iter(text).try_fold(0, |n, m| Ok(n + m.map(|_| 1)?))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    files: usize,
+    files_matched: usize,
+    lines: usize,
+    matches: usize,
+    bytes: u64,
+    elapsed: Duration,
+}
+
+// 読み込んだバイト数を数える。
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.set(self.count.get() + n as u64);
+        Ok(n)
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -53,6 +140,46 @@ pub fn get_args() -> MyResult<Config> {
                 .help("show line count")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("pcre")
+                .short("P")
+                .long("pcre")
+                .help("use a backtracking engine supporting lookaround and backreferences")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("files_with_matches")
+                .short("l")
+                .long("files-with-matches")
+                .help("print only names of files with matches")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("null")
+                .short("Z")
+                .long("null")
+                .help("print NUL after file names")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("null_data")
+                .short("z")
+                .long("null-data")
+                .help("input records are NUL-terminated")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("count_matches")
+                .long("count-matches")
+                .help("show match count")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("print search statistics")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("recursive")
                 .short("r")
@@ -60,6 +187,27 @@ pub fn get_args() -> MyResult<Config> {
                 .help("recursive")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("encoding")
+                .short("E")
+                .long("encoding")
+                .value_name("NAME")
+                .help("input encoding (e.g. utf-16le, shift_jis, euc-jp) [default: BOM sniffing]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("mmap")
+                .long("mmap")
+                .help("search regular files through a memory map")
+                .takes_value(false)
+                .conflicts_with("no_mmap"),
+        )
+        .arg(
+            Arg::with_name("no_mmap")
+                .long("no-mmap")
+                .help("never use a memory map")
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("files")
                 .value_name("FILE")
@@ -76,10 +224,23 @@ pub fn get_args() -> MyResult<Config> {
          */
 
     let pattern = matches.value_of_lossy("pattern").unwrap().to_string();
-    let pattern = RegexBuilder::new(&pattern)
-        .case_insensitive(matches.is_present("ignore_case"))
-        .build()
-        .map_err(|_| format!("invalid pattern: {}", &pattern))?;
+    let pattern = build_matcher(
+        &pattern,
+        matches.is_present("ignore_case"),
+        matches.is_present("pcre"),
+    )
+    .map_err(|_| format!("invalid pattern: {}", &pattern))?;
+    let encoding = matches
+        .value_of("encoding")
+        .map(parse_encoding)
+        .transpose()?;
+    let mmap = if matches.is_present("mmap") {
+        MmapChoice::Always
+    } else if matches.is_present("no_mmap") {
+        MmapChoice::Never
+    } else {
+        MmapChoice::Auto
+    };
     Ok(Config {
         pattern: pattern,
         files: matches.values_of_lossy("files").unwrap(),
@@ -87,40 +248,152 @@ pub fn get_args() -> MyResult<Config> {
         invert: matches.is_present("invert"),
         counts: matches.is_present("count"),
         recursive: matches.is_present("recursive"),
+        encoding,
+        mmap,
+        count_matches: matches.is_present("count_matches"),
+        stats: matches.is_present("stats"),
+        pcre: matches.is_present("pcre"),
+        files_with_matches: matches.is_present("files_with_matches"),
+        null: matches.is_present("null"),
+        null_data: matches.is_present("null_data"),
     })
 }
 
+fn build_matcher(pattern: &str, ignore_case: bool, pcre: bool) -> MyResult<Box<dyn Matcher>> {
+    if pcre {
+        let pattern = if ignore_case {
+            format!("(?i){}", pattern)
+        } else {
+            pattern.to_string()
+        };
+        Ok(Box::new(FancyRegex::new(&pattern)?))
+    } else {
+        Ok(Box::new(
+            RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()?,
+        ))
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
+    let start = Instant::now();
+    let mut stats = Stats::default();
     let entries = find_files(&config.files, config.recursive);
     let num_files = entries.len();
+    let separator = if config.null { '\0' } else { ':' };
     let print = | filename: &str, val: &str | {
         if num_files > 1 {
-            print!("{}:{}", filename, val);
+            print!("{}{}{}", filename, separator, val);
         } else {
             print!("{}", val);
         }
     };
+    // mmap検索用。行単位の検索と同じ結果になるよう、^は行頭にマッチさせる。
+    let candidate = (!config.pcre)
+        .then(|| {
+            RegexBuilder::new(config.pattern.as_str())
+                .case_insensitive(config.ignore_case)
+                .multi_line(true)
+                .build()
+        })
+        .transpose()?;
     for entry in entries {
         match entry {
             Err(e) => eprintln!("{}", e),
-            Ok(filename) => match open(&filename) {
+            Ok(filename) => match search(&filename, &config, candidate.as_ref()) {
                 Err(e) => eprintln!("{}", e),
-                Ok(file) => match find_lines(file, &config.pattern, config.invert) {
-                    Err(e) => eprintln!("{}", e),
-                    Ok(matches) => {
-                        if config.counts {
-                            print(&filename, &format!("{}\n", matches.len()));
-                        } else {
-                            matches.iter().for_each(|line| print(&filename, line));
+                Ok((matches, num_matches, bytes)) => {
+                    if config.files_with_matches {
+                        if !matches.is_empty() {
+                            print!("{}{}", filename, if config.null { '\0' } else { '\n' });
                         }
+                    } else if config.count_matches {
+                        print(&filename, &format!("{}\n", num_matches));
+                    } else if config.counts {
+                        print(&filename, &format!("{}\n", matches.len()));
+                    } else {
+                        matches.iter().for_each(|line| print(&filename, line));
+                    }
+                    stats.files += 1;
+                    if !matches.is_empty() {
+                        stats.files_matched += 1;
                     }
-                },
+                    stats.lines += matches.len();
+                    stats.matches += num_matches;
+                    stats.bytes += bytes;
+                }
             },
         }
     }
+    if config.stats {
+        stats.elapsed = start.elapsed();
+        print!("{}", format_stats(&stats));
+    }
     Ok(())
 }
 
+// 検索結果の行、マッチ数、読み込んだバイト数を返す。
+// マッチ数は行ごとに検索し直すので、--count-matchesか--statsのときだけ数える。
+// 後読みなどは行の境界を越えて評価されるため、-Pではmmapを使わない。
+fn search(
+    filename: &str,
+    config: &Config,
+    candidate: Option<&Regex>,
+) -> MyResult<(Vec<String>, usize, u64)> {
+    let pattern = config.pattern.as_ref();
+    let mmap = match candidate {
+        Some(_) => open_mmap(filename, config)?,
+        None => None,
+    };
+    let (matches, bytes) = match (candidate, mmap) {
+        (Some(candidate), Some(mmap)) => {
+            let matches = find_lines_mmap(&mmap, candidate, pattern, config.invert)?;
+            (matches, mmap.len() as u64)
+        }
+        _ => {
+            let bytes = Rc::new(Cell::new(0));
+            let file = open(filename, config.encoding, Rc::clone(&bytes))?;
+            let matches = if config.null_data {
+                find_records(file, pattern, config.invert, b'\0')?
+            } else {
+                find_lines(file, pattern, config.invert)?
+            };
+            (matches, bytes.get())
+        }
+    };
+    let num_matches = match config.count_matches || config.stats {
+        true => count_matches(&matches, pattern, config.invert)?,
+        false => 0,
+    };
+    Ok((matches, num_matches, bytes))
+}
+
+// 反転時は行そのものがマッチなので、行数を返す。
+fn count_matches<M: Matcher + ?Sized>(
+    lines: &[String],
+    pattern: &M,
+    invert_match: bool,
+) -> MyResult<usize> {
+    if invert_match {
+        Ok(lines.len())
+    } else {
+        lines.iter().map(|line| pattern.count(line)).sum()
+    }
+}
+
+fn format_stats(stats: &Stats) -> String {
+    format!(
+        "\n{} files searched\n{} files contained matches\n{} matched lines\n{} matches\n{} bytes searched\n{:.6} seconds\n",
+        stats.files,
+        stats.files_matched,
+        stats.lines,
+        stats.matches,
+        stats.bytes,
+        stats.elapsed.as_secs_f64(),
+    )
+}
+
 fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
     let filter_file = |entry: &DirEntry| return entry.file_type().is_file();
     paths
@@ -140,39 +413,148 @@ fn find_files(paths: &[String], recursive: bool) -> Vec<MyResult<String>> {
         .collect::<Vec<Result<String, _>>>()
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+fn parse_encoding(name: &str) -> MyResult<&'static Encoding> {
+    Encoding::for_label(name.as_bytes())
+        .ok_or_else(|| From::from(format!("invalid encoding: {}", name)))
+}
+
+// BOMがあればその符号化方式を優先し、UTF-8に変換して読み込む。
+fn open(
+    filename: &str,
+    encoding: Option<&'static Encoding>,
+    bytes: Rc<Cell<u64>>,
+) -> MyResult<Box<dyn BufRead>> {
+    let reader: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename)?),
+    };
+    let reader = CountingReader {
+        inner: reader,
+        count: bytes,
+    };
+    let decoder = DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(reader);
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+// mmapで検索できない場合はNoneを返し、read_lineでの検索に任せる。
+fn open_mmap(filename: &str, config: &Config) -> MyResult<Option<Mmap>> {
+    if filename == "-"
+        || config.mmap == MmapChoice::Never
+        || config.encoding.is_some()
+        || config.null_data
+        || is_anchored(config.pattern.as_str())
+    {
+        return Ok(None);
+    }
+    let file = File::open(filename)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file()
+        || metadata.len() == 0
+        || (config.mmap == MmapChoice::Auto && metadata.len() < MMAP_THRESHOLD)
+    {
+        return Ok(None);
     }
+    let mmap = unsafe { Mmap::map(&file)? };
+    if mmap.starts_with(b"\xFF\xFE") || mmap.starts_with(b"\xFE\xFF") {
+        return Ok(None);
+    }
+    Ok(Some(mmap))
+}
+
+// $, \A, \z はバッファ全体と行単位とで意味が変わるため、mmapでは扱わない。
+fn is_anchored(pattern: &str) -> bool {
+    pattern.contains('$') || pattern.contains("\\A") || pattern.contains("\\z")
 }
 
-fn find_lines<T: BufRead>(
+fn find_lines_mmap<M: Matcher + ?Sized>(
+    bytes: &[u8],
+    candidate: &Regex,
+    pattern: &M,
+    invert_match: bool,
+) -> MyResult<Vec<String>> {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let text = str::from_utf8(bytes).map_err(|_| invalid_utf8())?;
+    let line_end = |start: usize| {
+        text[start..]
+            .find('\n')
+            .map_or(text.len(), |i| start + i + 1)
+    };
+    let mut matches = vec![];
+    let mut pos = 0;
+
+    while pos < text.len() {
+        // 候補が見つかった行の先頭。候補がなければ末尾まで。
+        let start = candidate
+            .find_at(text, pos)
+            .map(|m| text[..m.start()].rfind('\n').map_or(0, |i| i + 1))
+            .filter(|&start| start < text.len())
+            .unwrap_or(text.len());
+        if invert_match {
+            matches.extend(text[pos..start].split_inclusive('\n').map(String::from));
+        }
+        if start == text.len() {
+            break;
+        }
+        let end = line_end(start);
+        let line = &text[start..end];
+        if pattern.is_match(line)? ^ invert_match {
+            matches.push(line.to_string());
+        }
+        pos = end;
+    }
+    Ok(matches)
+}
+
+// read_lineと同じエラーにする。
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
+
+fn find_lines<T: BufRead, M: Matcher + ?Sized>(
+    file: T,
+    pattern: &M,
+    invert_match: bool,
+) -> MyResult<Vec<String>> {
+    find_records(file, pattern, invert_match, b'\n')
+}
+
+// terminatorで区切られたレコードを検索する。レコードには終端文字を含む。
+fn find_records<T: BufRead, M: Matcher + ?Sized>(
     mut file: T,
-    pattern: &Regex,
+    pattern: &M,
     invert_match: bool,
+    terminator: u8,
 ) -> MyResult<Vec<String>> {
     let mut matches = vec![];
-    let mut line = String::new();
+    let mut record = vec![];
 
     loop {
-        let bytes = file.read_line(&mut line)?;
+        let bytes = file.read_until(terminator, &mut record)?;
         if bytes == 0 {
             break;
         }
-        if pattern.is_match(&line) ^ invert_match {
+        let line = str::from_utf8(&record).map_err(|_| invalid_utf8())?;
+        if pattern.is_match(line)? ^ invert_match {
             // 所有権の取得。cloneでも代用可能。
-            // matches.push(line.clone());
-            matches.push(mem::take(&mut line));
+            // matches.push(record.clone());
+            matches.push(String::from_utf8(mem::take(&mut record))?);
         }
-        line.clear();
+        record.clear();
     }
     Ok(matches)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{find_files, find_lines};
+    use super::{count_matches, find_files, find_lines, find_lines_mmap};
+    use fancy_regex::Regex as FancyRegex;
     use rand::{distributions::Alphanumeric, Rng};
     use regex::{Regex, RegexBuilder};
     use std::io::Cursor;
@@ -209,6 +591,52 @@ mod tests {
         assert_eq!(matches.unwrap().len(), 1);
     }
 
+    #[test]
+    fn test_find_lines_mmap() {
+        let text = "Lorem\nIpsum\r\nDOLOR\n\nsit amet\nor";
+        let patterns = ["or", "^or", "(?i)or", "m\\s", "r\\nI", "", "^$", "^"];
+
+        // mmapでの検索結果は行単位の検索と一致する
+        for pattern in patterns {
+            let re = Regex::new(pattern).unwrap();
+            let candidate = RegexBuilder::new(pattern)
+                .multi_line(true)
+                .build()
+                .unwrap();
+            for invert in [false, true] {
+                let expected = find_lines(Cursor::new(&text), &re, invert).unwrap();
+                let matches = find_lines_mmap(text.as_bytes(), &candidate, &re, invert);
+                assert!(matches.is_ok());
+                assert_eq!(matches.unwrap(), expected, "{} {}", pattern, invert);
+            }
+        }
+
+        // 後読みはバックトラック型のエンジンで扱う
+        let re = FancyRegex::new("(?<=L)or").unwrap();
+        let matches = find_lines(Cursor::new(&text), &re, false);
+        assert!(matches.is_ok());
+        assert_eq!(matches.unwrap(), vec!["Lorem\n"]);
+
+        // UTF-8以外の入力はエラーになる
+        let re = Regex::new("or").unwrap();
+        assert!(find_lines_mmap(b"Lorem\n\xFF\n", &re, &re, false).is_err());
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let text = b"Lorem\nIpsum\r\nDOLOR or door";
+        let re = Regex::new("or").unwrap();
+
+        // "Lorem"と"DOLOR or door"の2行で、マッチは3つ
+        let lines = find_lines(Cursor::new(&text), &re, false).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(count_matches(&lines, &re, false).unwrap(), 3);
+
+        // 反転時は行数を数える
+        let lines = find_lines(Cursor::new(&text), &re, true).unwrap();
+        assert_eq!(count_matches(&lines, &re, true).unwrap(), 1);
+    }
+
     #[test]
     fn test_find_files() {
         // Verify that the function finds a file known to exist
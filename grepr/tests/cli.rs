@@ -1,7 +1,7 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::{fs, path::Path};
+use std::{fs, path::Path, time::Instant};
 use sys_info::os_type;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
@@ -12,6 +12,10 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const NOBODY: &str = "tests/inputs/nobody.txt";
 const INPUTS_DIR: &str = "tests/inputs";
+const NEKO_SJIS: &str = "tests/encoded/neko.sjis.txt";
+const NEKO_EUCJP: &str = "tests/encoded/neko.eucjp.txt";
+const NEKO_UTF16LE: &str = "tests/encoded/neko.utf16le.txt";
+const NEKO_UTF16BE: &str = "tests/encoded/neko.utf16be.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -273,3 +277,207 @@ fn stdin_insensitive_count() -> TestResult {
         .stdout(expected);
     Ok(())
 }
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_encoding() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-E", "foo", "nyaa", NEKO_SJIS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid encoding: foo"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_shift_jis() -> TestResult {
+    run(
+        &["-E", "shift_jis", "ニャー", NEKO_SJIS],
+        "tests/expected/neko.nyaa",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_euc_jp() -> TestResult {
+    run(
+        &["--encoding", "euc-jp", "ニャー", NEKO_EUCJP],
+        "tests/expected/neko.nyaa",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_utf16le_bom() -> TestResult {
+    run(&["ニャー", NEKO_UTF16LE], "tests/expected/neko.nyaa")
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_utf16be_bom() -> TestResult {
+    run(&["ニャー", NEKO_UTF16BE], "tests/expected/neko.nyaa")
+}
+
+// --------------------------------------------------
+#[test]
+fn encoding_bom_overrides_option() -> TestResult {
+    run(
+        &["-E", "shift_jis", "ニャー", NEKO_UTF16LE],
+        "tests/expected/neko.nyaa",
+    )?;
+    run(
+        &["-E", "euc-jp", "ニャー", NEKO_UTF16BE],
+        "tests/expected/neko.nyaa",
+    )
+}
+
+// --------------------------------------------------
+fn gen_large_file() -> Result<String, Box<dyn std::error::Error>> {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("large.txt");
+    let mut contents = String::new();
+    for file in &[BUSTLE, FOX, NOBODY] {
+        contents += &fs::read_to_string(file)?;
+    }
+    fs::write(&path, contents.repeat(20_000))?;
+    Ok(path.display().to_string())
+}
+
+// --------------------------------------------------
+fn bench(args: &[&str]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let output = Command::cargo_bin(PRG)?.args(args).output()?;
+    eprintln!("{:?}: {:?}", args, start.elapsed());
+    assert!(output.status.success());
+    Ok(output.stdout)
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_matches_read_line() -> TestResult {
+    let large = gen_large_file()?;
+    for args in [vec!["the"], vec!["-i", "the"], vec!["-v", "the"], vec!["-c", "^The"]] {
+        let mmap = bench(&[&args[..], &["--mmap", &large]].concat())?;
+        let no_mmap = bench(&[&args[..], &["--no-mmap", &large]].concat())?;
+        assert_eq!(mmap, no_mmap);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mmap_small_file() -> TestResult {
+    run(
+        &["--mmap", "The", BUSTLE],
+        "tests/expected/bustle.txt.the.capitalized",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_mmap_no_mmap() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--mmap", "--no-mmap", "The", BUSTLE])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_matches_insensitive() -> TestResult {
+    run(
+        &["--count-matches", "-i", "the", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.insensitive.count_matches",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn stats_recursive() -> TestResult {
+    let expected = "\n4 files searched\n\
+        3 files contained matches\n\
+        7 matched lines\n\
+        10 matches\n\
+        477 bytes searched\n";
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "-i", "the", "-r", INPUTS_DIR])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected))
+        .stdout(predicate::str::is_match("[0-9]+[.][0-9]{6} seconds\n$")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_lookbehind_without_pcre() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["(?<=The )(b|m)", BUSTLE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid pattern: (?<=The )(b|m)"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn pcre_lookbehind() -> TestResult {
+    run(
+        &["-P", "(?<=The )(b|m)", BUSTLE],
+        "tests/expected/bustle.txt.pcre.lookbehind",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn pcre_backreference() -> TestResult {
+    run(
+        &["--pcre", "(\\w)\\1", NOBODY],
+        "tests/expected/nobody.txt.pcre.backref",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn pcre_insensitive_count() -> TestResult {
+    run(
+        &["-P", "-ci", "the(?= )", BUSTLE],
+        "tests/expected/bustle.txt.the.lowercase.insensitive.count",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn null_data() -> TestResult {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("records.bin");
+    fs::write(&path, "foo\nbar\0baz\0foo bar")?;
+    Command::cargo_bin(PRG)?
+        .args(["-z", "bar", &path.display().to_string()])
+        .assert()
+        .success()
+        .stdout("foo\nbar\0foo bar");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-l", "-r", "dog", INPUTS_DIR])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", Path::new(INPUTS_DIR).join("fox.txt").display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files_with_matches_null() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-lZ", "-r", "dog", INPUTS_DIR])
+        .assert()
+        .success()
+        .stdout(format!("{}\0", Path::new(INPUTS_DIR).join("fox.txt").display()));
+    Ok(())
+}